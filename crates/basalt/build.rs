@@ -0,0 +1,20 @@
+use basalt_resource::{pack::PACK_FILE, Compression, PackBuilder};
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    if std::env::var_os("CARGO_FEATURE_EMBED_ASSETS").is_none() {
+        return;
+    }
+
+    let assets = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../assets");
+    println!("cargo:rerun-if-changed={}", assets.display());
+
+    let mut builder = PackBuilder::new().with_zstd_level(19);
+    builder.add_dir(&assets, Compression::Zstd).expect("failed to pack assets");
+    for entry in builder.entries() {
+        println!("cargo:rerun-if-changed={}", assets.join(&entry.path).display());
+    }
+
+    let out = std::path::PathBuf::from(std::env::var_os("OUT_DIR").unwrap()).join(PACK_FILE);
+    builder.write_file(out).expect("failed to write asset pack");
+}
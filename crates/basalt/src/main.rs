@@ -1,4 +1,4 @@
-use log::info;
+use log::{error, info};
 use winit::{
     event::{ Event, WindowEvent, KeyboardInput, ElementState, VirtualKeyCode },
     event_loop::{ EventLoop, ControlFlow },
@@ -7,13 +7,13 @@ use winit::{
 
 use basalt_render::{render_state::RenderState, renderer::Renderer};
 
-async fn run() {
+async fn run(seed: u64) {
 
-    info!("Basalt Initialization Begin");
+    info!("Basalt Initialization Begin - seed {}", seed);
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new().with_title("Basalt").build(&event_loop).unwrap();
 
-    let mut state = RenderState::new(window).await;
+    let mut state = RenderState::new(window, seed).await;
     let mut renderer = Renderer::new(state.get_device(), state.get_queue(), state.get_config());
 
     info!("Basalt Loop Begin");
@@ -43,7 +43,7 @@ async fn run() {
             },
             Event::RedrawRequested(window_id) if window_id == state.get_window().id() => {
 
-                // state.update();
+                state.update();
 
                 match renderer.render(&state) {
                     Ok(_) => {}
@@ -66,5 +66,24 @@ fn main() {
 
     env_logger::init_from_env(env);
 
-    pollster::block_on(run());
+    // Loose asset folders and packs still overlay the embedded assets, so release builds can be patched
+    #[cfg(feature = "embed-assets")]
+    {
+        let embedded = basalt_resource::include_pack!(concat!(env!("OUT_DIR"), "/assets.bpak")).unwrap();
+        basalt_resource::roots::set_roots(basalt_resource::AssetRoots::discover().with_pack(embedded));
+    }
+
+    // The world seed can be passed as the first argument so a map can be reproduced
+    let seed = match std::env::args().nth(1) {
+        None => 0,
+        Some(arg) => match arg.parse() {
+            Ok(seed) => seed,
+            Err(e) => {
+                error!("Invalid seed `{}`, expected an unsigned integer: {}", arg, e);
+                std::process::exit(2);
+            },
+        },
+    };
+
+    pollster::block_on(run(seed));
 }
@@ -0,0 +1,201 @@
+use hex::{layout::Layout, map::HexMap};
+
+use crate::context::GenContext;
+use crate::mask::Shore;
+use crate::noise::{Fbm, Perlin};
+
+pub const MOISTURE_STAGE: &str = "moisture";
+pub const TEMPERATURE_STAGE: &str = "temperature";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Biome {
+    Ocean,
+    Beach,
+    Grassland,
+    Forest,
+    Desert,
+    Tundra,
+    Mountain,
+}
+
+/// Normalized climate of a single tile, both values in `0.0..=1.0`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Climate {
+    pub moisture: f64,
+    pub temperature: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClimateSettings {
+    #[serde(skip)]
+    pub layout: Layout,
+    pub moisture: Fbm,
+    pub temperature: Fbm,
+    /// How far the temperature noise can push a tile away from its latitude temperature
+    pub temperature_variation: f64,
+    /// World Z of the warmest latitude
+    pub equator: f32,
+    /// World distance from the equator to the coldest latitude
+    pub pole_distance: f32,
+    /// Temperature lost per world unit of height above sea level
+    pub lapse_rate: f64,
+    /// Moisture lost per world unit of height above sea level
+    pub moisture_falloff: f64,
+    #[serde(skip)]
+    pub sea_level: f32,
+}
+
+impl Default for ClimateSettings {
+    fn default() -> Self {
+        ClimateSettings {
+            layout: Layout::default(),
+            moisture: Fbm { frequency: 0.04, ..Fbm::default() },
+            temperature: Fbm { octaves: 3, frequency: 0.02, ..Fbm::default() },
+            temperature_variation: 0.3,
+            equator: 0.0,
+            pole_distance: 100.0,
+            lapse_rate: 0.2,
+            moisture_falloff: 0.1,
+            sea_level: 0.5,
+        }
+    }
+}
+
+/// Computes moisture and temperature for every tile of an elevation map
+pub fn climate(context: &GenContext, elevation: &HexMap<f32>, settings: &ClimateSettings) -> HexMap<Climate> {
+    let moisture_noise = Perlin::from_rng(&mut context.stage_rng(MOISTURE_STAGE));
+    let temperature_noise = Perlin::from_rng(&mut context.stage_rng(TEMPERATURE_STAGE));
+
+    elevation.map_values(|hex, &height| {
+        let (x, z) = settings.layout.hex_to_world(hex);
+        let (x, z) = (x as f64, z as f64);
+        let altitude = (height - settings.sea_level).max(0.0) as f64;
+
+        let latitude = ((z - settings.equator as f64).abs() / settings.pole_distance.max(f32::EPSILON) as f64).min(1.0);
+        let temperature = 1.0 - latitude
+            + settings.temperature.sample(&temperature_noise, x, z) * settings.temperature_variation
+            - altitude * settings.lapse_rate;
+
+        let moisture = settings.moisture.sample(&moisture_noise, x, z) * 0.5 + 0.5
+            - altitude * settings.moisture_falloff;
+
+        Climate { moisture: moisture.clamp(0.0, 1.0), temperature: temperature.clamp(0.0, 1.0) }
+    })
+}
+
+/// One cell of a Whittaker diagram, matching tiles colder than `max_temperature` and drier than `max_moisture`
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WhittakerCell {
+    pub max_temperature: f64,
+    pub max_moisture: f64,
+    pub biome: Biome,
+}
+
+/// Classifies tiles into biomes. Elevation decides ocean, beach and mountain tiles,
+/// every other tile takes the first Whittaker cell its climate falls into.
+#[derive(Debug, Clone, PartialEq)]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BiomeTable {
+    #[serde(skip)]
+    pub sea_level: f32,
+    /// Tiles less than this height above sea level are beaches
+    pub beach_height: f32,
+    pub mountain_level: f32,
+    pub cells: Vec<WhittakerCell>,
+    /// Used when no cell matches
+    pub fallback: Biome,
+}
+
+impl Default for BiomeTable {
+    fn default() -> Self {
+        let cell = |max_temperature, max_moisture, biome| WhittakerCell { max_temperature, max_moisture, biome };
+        BiomeTable {
+            sea_level: 0.5,
+            beach_height: 0.25,
+            mountain_level: 1.5,
+            cells: vec![
+                cell(0.25, 1.0, Biome::Tundra),
+                cell(0.6, 0.6, Biome::Grassland),
+                cell(0.6, 1.0, Biome::Forest),
+                cell(1.0, 0.3, Biome::Desert),
+                cell(1.0, 0.6, Biome::Grassland),
+                cell(1.0, 1.0, Biome::Forest),
+            ],
+            fallback: Biome::Grassland,
+        }
+    }
+}
+
+impl BiomeTable {
+    pub fn classify(&self, height: f32, climate: &Climate) -> Biome {
+        if height < self.sea_level {
+            return Biome::Ocean;
+        }
+        if height < self.sea_level + self.beach_height {
+            return Biome::Beach;
+        }
+        if height >= self.mountain_level {
+            return Biome::Mountain;
+        }
+
+        self.cells.iter()
+            .find(|c| climate.temperature <= c.max_temperature && climate.moisture <= c.max_moisture)
+            .map_or(self.fallback, |c| c.biome)
+    }
+}
+
+pub fn classify(elevation: &HexMap<f32>, climate: &HexMap<Climate>, table: &BiomeTable) -> HexMap<Biome> {
+    elevation.map_values(|hex, &height| table.classify(height, &climate[hex]))
+}
+
+/// Makes open ocean and coastline tiles from `mask::shoreline` into ocean and beach biomes,
+/// so the sea follows the landmass shape rather than height alone. Mountains on the coast are kept.
+pub fn apply_shoreline(biomes: &mut HexMap<Biome>, shore: &HexMap<Shore>) {
+    for (hex, biome) in biomes.iter_mut() {
+        match shore.get(hex) {
+            Some(Shore::Ocean) => *biome = Biome::Ocean,
+            Some(Shore::Coast) if *biome != Biome::Mountain => *biome = Biome::Beach,
+            _ => {},
+        }
+    }
+}
+
+/// How a biome is drawn. `material` names the material to draw the tile with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BiomeAppearance {
+    pub color: [f32; 3],
+    pub material: String,
+}
+
+impl Biome {
+    pub const ALL: [Biome; 7] = [
+        Biome::Ocean,
+        Biome::Beach,
+        Biome::Grassland,
+        Biome::Forest,
+        Biome::Desert,
+        Biome::Tundra,
+        Biome::Mountain,
+    ];
+
+    /// The default appearance of the biome
+    pub fn appearance(self) -> BiomeAppearance {
+        let (color, material) = match self {
+            Biome::Ocean => ([0.05, 0.15, 0.4], "water"),
+            Biome::Beach => ([0.76, 0.7, 0.5], "sand"),
+            Biome::Grassland => ([0.3, 0.55, 0.2], "grass"),
+            Biome::Forest => ([0.0, 0.2, 0.1], "forest"),
+            Biome::Desert => ([0.85, 0.65, 0.35], "sand"),
+            Biome::Tundra => ([0.75, 0.8, 0.8], "snow"),
+            Biome::Mountain => ([0.45, 0.42, 0.4], "rock"),
+        };
+        BiomeAppearance { color, material: material.to_owned() }
+    }
+}
@@ -0,0 +1,143 @@
+use hex::{hexagon::Axial, map::HexMap};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ErosionSettings {
+    pub iterations: u32,
+    /// Water added to every land tile each iteration
+    pub rainfall: f32,
+    /// Scales how much material flowing water carries away
+    pub erosion_rate: f32,
+    /// Fraction of eroded material deposited on the downhill neighbor, the rest is washed away
+    pub deposition: f32,
+    /// Upper bound on the height a single tile can lose in one iteration
+    pub max_erosion: f32,
+    /// Tiles below sea level collect sediment but do not erode
+    #[serde(skip)]
+    pub sea_level: f32,
+}
+
+impl Default for ErosionSettings {
+    fn default() -> Self {
+        ErosionSettings {
+            iterations: 20,
+            rainfall: 1.0,
+            erosion_rate: 0.02,
+            deposition: 0.5,
+            max_erosion: 0.05,
+            sea_level: 0.5,
+        }
+    }
+}
+
+/// Orders tiles from highest to lowest. The sort is stable, so ties stay in coordinate order and resolve the same way every run
+pub(crate) fn highest_first(heights: &HexMap<f32>) -> Vec<Axial<i32>> {
+    let mut order = heights.coords().collect::<Vec<_>>();
+    order.sort_by(|a, b| heights[*b].total_cmp(&heights[*a]));
+    order
+}
+
+/// The lowest neighbor that is lower than `hex`, if any
+pub(crate) fn downhill(heights: &HexMap<f32>, hex: Axial<i32>) -> Option<Axial<i32>> {
+    let height = heights[hex];
+    hex.neighbors().into_iter()
+        .filter_map(|n| heights.get(n).map(|&h| (n, h)))
+        .filter(|&(_, h)| h < height)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(n, _)| n)
+}
+
+/// Grid based hydraulic erosion. Each iteration rains on every land tile, routes the water down the steepest
+/// slope to accumulate flow, then carves each tile in proportion to its flow and slope and deposits part
+/// of the sediment downhill.
+pub fn erode(heights: &mut HexMap<f32>, settings: &ErosionSettings) {
+    for _ in 0..settings.iterations {
+        let order = highest_first(heights);
+        let downhill = heights.map_values(|hex, _| downhill(heights, hex));
+
+        let mut flow = heights.map_values(|_, &h| if h >= settings.sea_level { settings.rainfall } else { 0.0 });
+        for &hex in &order {
+            if let Some(next) = downhill[hex] {
+                flow[next] += flow[hex];
+            }
+        }
+
+        // Changes are gathered first so the result does not depend on the order tiles are visited in
+        let mut delta = heights.map_values(|_, _| 0.0);
+        for &hex in &order {
+            let height = heights[hex];
+            let Some(next) = downhill[hex] else { continue };
+            if height < settings.sea_level {
+                continue;
+            }
+
+            let slope = height - heights[next];
+            let amount = (settings.erosion_rate * flow[hex].sqrt() * slope)
+                .min(slope * 0.5)
+                .min(settings.max_erosion);
+
+            delta[hex] -= amount;
+            delta[next] += amount * settings.deposition;
+        }
+
+        for (height, change) in heights.values_mut().zip(delta.values()) {
+            *height += change;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex::map::MapShape;
+
+    use super::*;
+
+    fn hills() -> HexMap<f32> {
+        HexMap::from_fn(MapShape::Hexagon { radius: 6 }, |hex| {
+            let wave = ((hex.q() * 5 + hex.r() * 3).rem_euclid(7)) as f32 * 0.05;
+            1.5 - 0.1 * Axial::new(0, 0).distance(&hex) as f32 + wave
+        })
+    }
+
+    fn total(heights: &HexMap<f32>) -> f64 {
+        heights.values().map(|&h| h as f64).sum()
+    }
+
+    #[test]
+    fn full_deposition_keeps_total_mass() {
+        let mut heights = hills();
+        let before = total(&heights);
+        let settings = ErosionSettings { deposition: 1.0, sea_level: 0.0, ..ErosionSettings::default() };
+        erode(&mut heights, &settings);
+
+        assert!((total(&heights) - before).abs() < 1e-3, "{before} became {}", total(&heights));
+        assert_ne!(heights.values().copied().collect::<Vec<_>>(), hills().into_values());
+    }
+
+    #[test]
+    fn washed_away_sediment_is_bounded() {
+        let mut heights = hills();
+        let before = total(&heights);
+        let settings = ErosionSettings { deposition: 0.5, sea_level: 0.0, ..ErosionSettings::default() };
+        erode(&mut heights, &settings);
+
+        // At most half of what each tile loses per iteration is washed away, and nothing is ever added
+        let lost = before - total(&heights);
+        let bound = (heights.len() as u32 * settings.iterations) as f64 * settings.max_erosion as f64 * 0.5;
+        assert!(lost > 0.0 && lost <= bound + 1e-3, "lost {lost}, bound {bound}");
+    }
+
+    #[test]
+    fn erosion_never_digs_below_the_downhill_neighbor() {
+        let original = hills();
+        let mut heights = original.clone();
+        erode(&mut heights, &ErosionSettings { iterations: 1, sea_level: 0.0, ..ErosionSettings::default() });
+
+        for hex in original.coords() {
+            if let Some(next) = downhill(&original, hex) {
+                assert!(heights[hex] >= original[next] - 1e-6, "{hex:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use hex::{layout::Layout, map::{HexMap, MapShape}};
+
+use crate::context::GenContext;
+use crate::noise::{Fbm, Perlin};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeightmapSettings {
+    pub fbm: Fbm,
+    /// Places hexes in the noise field, so the noise frequency is relative to world units
+    pub layout: Layout,
+    /// Number of discrete elevation levels, the lowest being level 0
+    pub levels: u32,
+    /// World space height of a single elevation level
+    pub step_height: f32,
+    /// Stretches the noise around its midpoint before quantizing, since summed octaves rarely reach their extremes.
+    /// `1.0` keeps the noise as sampled.
+    pub contrast: f64,
+}
+
+impl Default for HeightmapSettings {
+    fn default() -> Self {
+        HeightmapSettings { fbm: Fbm::default(), layout: Layout::default(), levels: 8, step_height: 0.25, contrast: 2.0 }
+    }
+}
+
+impl HeightmapSettings {
+    /// Maps a normalized height in `0.0..=1.0` to an elevation level
+    pub fn level(&self, height: f64) -> u32 {
+        let levels = self.levels.max(1);
+        ((height.clamp(0.0, 1.0) * levels as f64) as u32).min(levels - 1)
+    }
+
+    /// World space height of an elevation level
+    pub fn level_height(&self, level: u32) -> f32 {
+        level as f32 * self.step_height
+    }
+
+    /// Snaps a world space height down to its elevation level
+    pub fn quantize(&self, height: f32) -> f32 {
+        let max_height = self.level_height(self.levels.max(1));
+        self.level_height(self.level(height as f64 / max_height.max(f32::EPSILON) as f64))
+    }
+
+    /// World space height of a normalized height before it is quantized
+    pub fn raw_height(&self, height: f64) -> f32 {
+        (height.clamp(0.0, 1.0) * self.levels.max(1) as f64) as f32 * self.step_height
+    }
+}
+
+pub const STAGE: &str = "heightmap";
+
+/// Samples the noise field at every hex in `map`, returning heights normalized to `0.0..=1.0`
+pub fn sample_heights<T>(context: &GenContext, map: &HexMap<T>, settings: &HeightmapSettings) -> HexMap<f64> {
+    let noise = Perlin::from_rng(&mut context.stage_rng(STAGE));
+    map.map_values(|hex, _| {
+        let (x, z) = settings.layout.hex_to_world(hex);
+        (settings.fbm.sample(&noise, x as f64, z as f64) * settings.contrast * 0.5 + 0.5).clamp(0.0, 1.0)
+    })
+}
+
+/// Generates quantized world space elevations for every hex of `shape`
+pub fn generate(context: &GenContext, shape: MapShape, settings: &HeightmapSettings) -> HexMap<f32> {
+    let heights = sample_heights(context, &HexMap::filled(shape, ()), settings);
+    heights.map_values(|_, &h| settings.level_height(settings.level(h)))
+}
+
+/// Generates world space elevations that are not yet quantized, for stages such as erosion that need smooth heights.
+/// Pass the result through `HeightmapSettings::quantize` once those stages are done.
+pub fn generate_raw(context: &GenContext, shape: MapShape, settings: &HeightmapSettings) -> HexMap<f32> {
+    let heights = sample_heights(context, &HexMap::filled(shape, ()), settings);
+    heights.map_values(|_, &h| settings.raw_height(h))
+}
+
+/// Writes quantized elevations into an existing map through `write`
+pub fn apply<T, F>(context: &GenContext, map: &mut HexMap<T>, settings: &HeightmapSettings, mut write: F)
+where
+    F: FnMut(&mut T, f32),
+{
+    let heights = sample_heights(context, map, settings);
+    for ((_, tile), &height) in map.iter_mut().zip(heights.values()) {
+        write(tile, settings.level_height(settings.level(height)));
+    }
+}
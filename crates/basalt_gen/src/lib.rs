@@ -0,0 +1,11 @@
+
+pub mod biome;
+pub mod context;
+pub mod erosion;
+pub mod heightmap;
+pub mod mask;
+pub mod noise;
+pub mod pipeline;
+pub mod preview;
+pub mod rivers;
+pub mod wfc;
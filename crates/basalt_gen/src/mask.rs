@@ -0,0 +1,247 @@
+use hex::{hexagon::Axial, layout::Layout, map::HexMap};
+use rand::Rng;
+
+use crate::context::GenContext;
+
+pub const CONTINENTS_STAGE: &str = "continents";
+
+/// How a mask value `m` combines with a normalized height `h`, both in `0.0..=1.0`
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlendMode {
+    /// `h * m`, fades terrain out where the mask is dark
+    Multiply,
+    Min,
+    Max,
+    /// `h + m`, raising land where the mask is bright
+    Add,
+    /// `h - (1 - m)`, sinking land where the mask is dark
+    Subtract,
+    /// Moves the height towards the mask by the given weight
+    Lerp(f64),
+}
+
+impl BlendMode {
+    pub fn blend(&self, height: f64, mask: f64) -> f64 {
+        let blended = match *self {
+            BlendMode::Multiply => height * mask,
+            BlendMode::Min => height.min(mask),
+            BlendMode::Max => height.max(mask),
+            BlendMode::Add => height + mask,
+            BlendMode::Subtract => height - (1.0 - mask),
+            BlendMode::Lerp(weight) => height + (mask - height) * weight,
+        };
+        blended.clamp(0.0, 1.0)
+    }
+}
+
+/// A grayscale image stretched over a world space rectangle
+#[derive(Debug, Clone)]
+pub struct ImageMask {
+    pixels: image::GrayImage,
+    /// World XZ of the image's top left corner
+    pub origin: (f32, f32),
+    /// World size covered by the whole image
+    pub size: (f32, f32),
+}
+
+impl ImageMask {
+    pub fn from_bytes(bytes: &[u8], origin: (f32, f32), size: (f32, f32)) -> anyhow::Result<Self> {
+        let pixels = image::load_from_memory(bytes)?.to_luma8();
+        Ok(ImageMask { pixels, origin, size })
+    }
+
+    /// Loads an image from the assets folder
+    pub fn load(file_name: &str, origin: (f32, f32), size: (f32, f32)) -> anyhow::Result<Self> {
+        Self::from_bytes(&basalt_resource::load_binary(file_name)?, origin, size)
+    }
+
+    /// Samples the nearest pixel, treating everything outside the image as black
+    pub fn sample(&self, x: f32, z: f32) -> f64 {
+        let u = (x - self.origin.0) / self.size.0;
+        let v = (z - self.origin.1) / self.size.1;
+        if !(0.0..1.0).contains(&u) || !(0.0..1.0).contains(&v) {
+            return 0.0;
+        }
+
+        let px = (u * self.pixels.width() as f32) as u32;
+        let py = (v * self.pixels.height() as f32) as u32;
+        self.pixels.get_pixel(px, py).0[0] as f64 / 255.0
+    }
+}
+
+/// Shapes the overall landmass. Every mask evaluates to `0.0` for open sea and `1.0` for full land.
+#[derive(Debug, Clone)]
+pub enum Mask {
+    /// A single island fading out towards `radius` from `center`. Higher `falloff` values give steeper coasts.
+    Radial { center: (f32, f32), radius: f32, falloff: f64 },
+    /// `count` continents around random seed points within `spread` of the origin, each tile taking the
+    /// falloff from its nearest seed. A `spread` that is not a positive number puts every seed at the origin.
+    Continents { count: u32, spread: f32, radius: f32, falloff: f64 },
+    Image(ImageMask),
+}
+
+// Masks avoid `powf` and `hypot`, whose results come from the platform's libm and can differ in the last bit,
+// and stick to multiplication and square roots, which IEEE 754 rounds exactly
+
+/// Bits of the fractional part of an exponent that `unit_pow` honors
+const FRACTION_BITS: u32 = 24;
+
+/// `base.powf(exponent)` for a `base` in `0.0..=1.0`. The whole part of the exponent is applied by squaring
+/// and each bit of the fractional part by a further square root of the base.
+fn unit_pow(base: f64, exponent: f64) -> f64 {
+    if exponent < 0.0 {
+        return 1.0 / unit_pow(base, -exponent);
+    }
+
+    let mut result = 1.0;
+    let mut whole = exponent.trunc() as u64;
+    let mut square = base;
+    while whole > 0 {
+        if whole & 1 == 1 {
+            result *= square;
+        }
+        square *= square;
+        whole >>= 1;
+    }
+
+    let mut fraction = exponent.fract();
+    let mut root = base;
+    for _ in 0..FRACTION_BITS {
+        if fraction <= 0.0 {
+            break;
+        }
+        root = root.sqrt();
+        fraction *= 2.0;
+        if fraction >= 1.0 {
+            result *= root;
+            fraction -= 1.0;
+        }
+    }
+    result
+}
+
+fn distance_squared(a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (dx, dz) = (a.0 - b.0, a.1 - b.1);
+    dx * dx + dz * dz
+}
+
+fn falloff(distance: f32, radius: f32, exponent: f64) -> f64 {
+    let t = (distance / radius.max(f32::EPSILON)).min(1.0) as f64;
+    1.0 - unit_pow(t, exponent)
+}
+
+impl Mask {
+    /// Evaluates the mask for every tile of `map`. Continent seeds are drawn from the context's continents stage.
+    pub fn evaluate<T>(&self, context: &GenContext, map: &HexMap<T>, layout: &Layout) -> HexMap<f64> {
+        let seeds = match *self {
+            Mask::Continents { count, spread, .. } => {
+                let mut rng = context.stage_rng(CONTINENTS_STAGE);
+                let mut offset = || if spread.is_finite() && spread > 0.0 { rng.gen_range(-spread..=spread) } else { 0.0 };
+                (0..count).map(|_| (offset(), offset())).collect::<Vec<_>>()
+            },
+            _ => Vec::new(),
+        };
+
+        map.map_values(|hex: Axial<i32>, _| {
+            let (x, z) = layout.hex_to_world(hex);
+            match self {
+                Mask::Radial { center, radius, falloff: exponent } => {
+                    falloff(distance_squared((x, z), *center).sqrt(), *radius, *exponent)
+                },
+                Mask::Continents { radius, falloff: exponent, .. } => {
+                    let nearest = seeds.iter()
+                        .map(|&s| distance_squared((x, z), s))
+                        .fold(f32::INFINITY, f32::min);
+                    falloff(nearest.sqrt(), *radius, *exponent)
+                },
+                Mask::Image(image) => image.sample(x, z),
+            }
+        })
+    }
+
+    /// Blends the mask into normalized heights, such as those from `heightmap::sample_heights`
+    pub fn apply(&self, context: &GenContext, heights: &mut HexMap<f64>, layout: &Layout, blend: BlendMode) {
+        let mask = self.evaluate(context, heights, layout);
+        for (height, &m) in heights.values_mut().zip(mask.values()) {
+            *height = blend.blend(*height, m);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Shore {
+    /// Below sea level and connected to the edge of the map
+    Ocean,
+    /// Land next to the ocean
+    Coast,
+    /// Below sea level but enclosed by land
+    Inland,
+    Land,
+}
+
+/// Splits a heightmap into open ocean, coastline and land. Ocean floods in from the map edge, so
+/// enclosed basins below sea level are reported as `Inland` water rather than ocean.
+pub fn shoreline(heights: &HexMap<f32>, sea_level: f32) -> HexMap<Shore> {
+    let mut shore = heights.map_values(|_, &h| if h < sea_level { Shore::Inland } else { Shore::Land });
+
+    let mut open = heights.coords()
+        .filter(|&hex| heights[hex] < sea_level && hex.neighbors().iter().any(|&n| !heights.contains(n)))
+        .collect::<Vec<_>>();
+    while let Some(hex) = open.pop() {
+        if shore[hex] != Shore::Inland {
+            continue;
+        }
+        shore[hex] = Shore::Ocean;
+        open.extend(hex.neighbors().into_iter().filter(|&n| shore.get(n) == Some(&Shore::Inland)));
+    }
+
+    let coast = heights.coords()
+        .filter(|&hex| shore[hex] == Shore::Land && hex.neighbors().iter().any(|&n| shore.get(n) == Some(&Shore::Ocean)))
+        .collect::<Vec<_>>();
+    for hex in coast {
+        shore[hex] = Shore::Coast;
+    }
+
+    shore
+}
+
+#[cfg(test)]
+mod tests {
+    use hex::map::MapShape;
+
+    use super::*;
+
+    #[test]
+    fn unit_pow_matches_powf() {
+        for exponent in [0.0, 0.5, 1.0, 1.5, 2.0, 2.75, 3.0, 7.0, 0.1, -1.0, -0.5] {
+            for i in 0..=20 {
+                let base = i as f64 / 20.0;
+                let expected = base.powf(exponent);
+                let found = unit_pow(base, exponent);
+                let tolerance = 1e-6 * expected.abs().max(1.0);
+                assert!((found - expected).abs() <= tolerance || found == expected, "{base}^{exponent}: {found} != {expected}");
+            }
+        }
+    }
+
+    #[test]
+    fn whole_exponents_only_multiply() {
+        let t = 0.3_f64;
+        assert_eq!(unit_pow(t, 2.0), t * t);
+        assert_eq!(unit_pow(t, 3.0), t * t * t);
+        assert_eq!(unit_pow(t, 0.5), t.sqrt());
+    }
+
+    #[test]
+    fn invalid_spread_does_not_panic() {
+        let context = GenContext::new(7);
+        let map = HexMap::filled(MapShape::Hexagon { radius: 3 }, ());
+        for spread in [-4.0, 0.0, f32::NAN, f32::INFINITY] {
+            let mask = Mask::Continents { count: 3, spread, radius: 5.0, falloff: 2.0 };
+            let values = mask.evaluate(&context, &map, &Layout::default());
+            assert_eq!(values[Axial::new(0, 0)], 1.0);
+        }
+    }
+}
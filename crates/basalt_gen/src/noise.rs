@@ -0,0 +1,113 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+// Gradient noise is implemented here rather than pulled from a crate so that its output only depends on
+// IEEE add, multiply and floor, which keeps generated terrain identical on every platform.
+
+const GRADIENTS: [(f64, f64); 8] = [
+    (1.0, 1.0), (-1.0, 1.0), (1.0, -1.0), (-1.0, -1.0),
+    (1.0, 0.0), (-1.0, 0.0), (0.0, 1.0), (0.0, -1.0),
+];
+
+/// 2D improved Perlin noise with a seeded permutation table
+#[derive(Clone)]
+pub struct Perlin {
+    permutation: [u8; 512],
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        Self::from_rng(&mut ChaCha8Rng::seed_from_u64(seed))
+    }
+
+    pub fn from_rng<R: Rng>(rng: &mut R) -> Self {
+        let mut table: [u8; 256] = std::array::from_fn(|i| i as u8);
+
+        // Fisher-Yates shuffle using fixed-width indices so the table does not depend on the platform's usize
+        for i in (1..table.len()).rev() {
+            table.swap(i, rng.gen_range(0..=i as u32) as usize);
+        }
+
+        Perlin { permutation: std::array::from_fn(|i| table[i & 255]) }
+    }
+
+    fn hash(&self, x: i32, y: i32) -> usize {
+        let x = (x & 255) as usize;
+        let y = (y & 255) as usize;
+        self.permutation[self.permutation[x] as usize + y] as usize
+    }
+
+    fn gradient(&self, x: i32, y: i32, dx: f64, dy: f64) -> f64 {
+        let (gx, gy) = GRADIENTS[self.hash(x, y) & 7];
+        gx * dx + gy * dy
+    }
+
+    /// Samples the noise at a point, returning a value roughly in `-1.0..=1.0`
+    pub fn get(&self, x: f64, y: f64) -> f64 {
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let (dx, dy) = (x - x0, y - y0);
+        let (ix, iy) = (x0 as i32, y0 as i32);
+
+        let n00 = self.gradient(ix, iy, dx, dy);
+        let n10 = self.gradient(ix + 1, iy, dx - 1.0, dy);
+        let n01 = self.gradient(ix, iy + 1, dx, dy - 1.0);
+        let n11 = self.gradient(ix + 1, iy + 1, dx - 1.0, dy - 1.0);
+
+        let u = fade(dx);
+        let v = fade(dy);
+
+        lerp(lerp(n00, n10, u), lerp(n01, n11, u), v)
+    }
+}
+
+#[inline]
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+#[inline]
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+/// Fractional Brownian motion settings, summing octaves of noise at increasing frequency and decreasing amplitude
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Fbm {
+    pub octaves: u32,
+    /// Base frequency of the first octave, in noise cycles per world unit
+    pub frequency: f64,
+    /// Frequency multiplier between octaves
+    pub lacunarity: f64,
+    /// Amplitude multiplier between octaves
+    pub gain: f64,
+}
+
+impl Default for Fbm {
+    fn default() -> Self {
+        Fbm { octaves: 5, frequency: 0.05, lacunarity: 2.0, gain: 0.5 }
+    }
+}
+
+impl Fbm {
+    /// Samples the summed octaves, normalized back into roughly `-1.0..=1.0`
+    pub fn sample(&self, noise: &Perlin, x: f64, y: f64) -> f64 {
+        let mut frequency = self.frequency;
+        let mut amplitude = 1.0;
+        let mut total = 0.0;
+        let mut amplitude_sum = 0.0;
+
+        for octave in 0..self.octaves {
+            // Offset each octave so the lattice points of different octaves do not line up at the origin
+            let offset = octave as f64 * 17.31;
+            total += noise.get(x * frequency + offset, y * frequency + offset) * amplitude;
+            amplitude_sum += amplitude;
+            frequency *= self.lacunarity;
+            amplitude *= self.gain;
+        }
+
+        if amplitude_sum > 0.0 { total / amplitude_sum } else { 0.0 }
+    }
+}
@@ -0,0 +1,488 @@
+use anyhow::Context;
+use hex::{layout::Layout, map::{HexMap, MapShape}};
+use rand::Rng;
+
+use crate::biome::{self, Biome, BiomeTable, Climate, ClimateSettings};
+use crate::context::GenContext;
+use crate::erosion::{self, ErosionSettings};
+use crate::heightmap::{self, HeightmapSettings};
+use crate::mask::{self, BlendMode, ImageMask, Mask};
+use crate::noise::Fbm;
+use crate::rivers::{self, Hydrology, RiverSettings};
+
+/// Landmass masks as written in a pipeline file. Image masks name a file in the assets folder.
+#[derive(Debug, Clone, PartialEq)]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum MaskConfig {
+    Radial { center: (f32, f32), radius: f32, falloff: f64 },
+    Continents { count: u32, spread: f32, radius: f32, falloff: f64 },
+    Image { file: String, origin: (f32, f32), size: (f32, f32) },
+}
+
+impl MaskConfig {
+    pub fn build(&self) -> anyhow::Result<Mask> {
+        Ok(match self {
+            MaskConfig::Radial { center, radius, falloff } => {
+                Mask::Radial { center: *center, radius: *radius, falloff: *falloff }
+            },
+            MaskConfig::Continents { count, spread, radius, falloff } => {
+                if !(spread.is_finite() && *spread >= 0.0) {
+                    anyhow::bail!("continents `spread` must be a non-negative number, found {spread}");
+                }
+                Mask::Continents { count: *count, spread: *spread, radius: *radius, falloff: *falloff }
+            },
+            MaskConfig::Image { file, origin, size } => {
+                let image = ImageMask::load(file, *origin, *size)
+                    .with_context(|| format!("failed to load mask image `{file}`"))?;
+                Mask::Image(image)
+            },
+        })
+    }
+}
+
+/// A single generation step. Stages run in the order they are listed and each one works on the output of the last.
+/// In a pipeline file every stage is a table with a `stage` key naming which step it is.
+#[derive(Debug, Clone, PartialEq)]
+#[derive(serde::Serialize)]
+#[serde(tag = "stage", rename_all = "snake_case")]
+pub enum Stage {
+    /// Replaces the heights with fractal noise
+    Noise {
+        #[serde(default)]
+        fbm: Fbm,
+        #[serde(default = "default_contrast")]
+        contrast: f64,
+    },
+    /// Blends a landmass mask into the heights
+    Mask { mask: MaskConfig, blend: BlendMode },
+    Erosion(ErosionSettings),
+    /// Computes flow, lakes and rivers. Rainfall follows moisture if a biome stage ran first.
+    Rivers(RiverSettings),
+    /// Computes the climate and classifies every tile into a biome
+    Biome {
+        #[serde(default)]
+        climate: ClimateSettings,
+        #[serde(default)]
+        table: BiomeTable,
+        /// Turns ocean connected to the map edge and its coastline into ocean and beach biomes
+        #[serde(default = "default_true")]
+        shoreline: bool,
+    },
+    /// Snaps the heights to elevation levels
+    Quantize,
+    /// Places a named feature on a random `density` fraction of the tiles in `biomes`, skipping
+    /// tiles that already hold a feature or carry a lake or river
+    Scatter { feature: String, biomes: Vec<Biome>, density: f64 },
+}
+
+// Stages are read by hand rather than as an internally tagged enum, which serde buffers before picking the
+// variant and so loses the position of any error inside the stage. Here only the keys before `stage` are
+// buffered and the rest of the table goes straight to the fields of that variant, so errors point at the
+// offending key whenever `stage` comes first, as it does in every shipped pipeline.
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum StageName {
+    Noise,
+    Mask,
+    Erosion,
+    Rivers,
+    Biome,
+    Quantize,
+    Scatter,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NoiseFields {
+    #[serde(default)]
+    fbm: Fbm,
+    #[serde(default = "default_contrast")]
+    contrast: f64,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MaskFields {
+    mask: MaskConfig,
+    blend: BlendMode,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BiomeFields {
+    #[serde(default)]
+    climate: ClimateSettings,
+    #[serde(default)]
+    table: BiomeTable,
+    #[serde(default = "default_true")]
+    shoreline: bool,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct QuantizeFields {}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScatterFields {
+    feature: String,
+    biomes: Vec<Biome>,
+    density: f64,
+}
+
+/// Feeds the keys buffered before `stage` to a variant's fields, followed by the rest of the table
+struct BufferedMap<A> {
+    buffered: std::vec::IntoIter<(String, toml::Value)>,
+    value: Option<toml::Value>,
+    rest: A,
+}
+
+impl<'de, A: serde::de::MapAccess<'de>> serde::de::MapAccess<'de> for BufferedMap<A> {
+    type Error = A::Error;
+
+    fn next_key_seed<K: serde::de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, A::Error> {
+        use serde::de::IntoDeserializer;
+
+        match self.buffered.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(key.into_deserializer()).map(Some)
+            },
+            None => self.rest.next_key_seed(seed),
+        }
+    }
+
+    fn next_value_seed<V: serde::de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, A::Error> {
+        match self.value.take() {
+            Some(value) => seed.deserialize(value).map_err(serde::de::Error::custom),
+            None => self.rest.next_value_seed(seed),
+        }
+    }
+}
+
+struct StageVisitor;
+
+impl<'de> serde::de::Visitor<'de> for StageVisitor {
+    type Value = Stage;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "a stage table")
+    }
+
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<Stage, A::Error> {
+        use serde::de::{Deserialize, Error, value::MapAccessDeserializer};
+
+        let mut buffered = Vec::new();
+        let name = loop {
+            match map.next_key::<String>()? {
+                Some(key) if key == "stage" => break map.next_value::<StageName>()?,
+                Some(key) => buffered.push((key, map.next_value::<toml::Value>()?)),
+                None => return Err(A::Error::missing_field("stage")),
+            }
+        };
+
+        let fields = MapAccessDeserializer::new(BufferedMap { buffered: buffered.into_iter(), value: None, rest: map });
+        Ok(match name {
+            StageName::Noise => {
+                let NoiseFields { fbm, contrast } = NoiseFields::deserialize(fields)?;
+                Stage::Noise { fbm, contrast }
+            },
+            StageName::Mask => {
+                let MaskFields { mask, blend } = MaskFields::deserialize(fields)?;
+                Stage::Mask { mask, blend }
+            },
+            StageName::Erosion => Stage::Erosion(ErosionSettings::deserialize(fields)?),
+            StageName::Rivers => Stage::Rivers(RiverSettings::deserialize(fields)?),
+            StageName::Biome => {
+                let BiomeFields { climate, table, shoreline } = BiomeFields::deserialize(fields)?;
+                Stage::Biome { climate, table, shoreline }
+            },
+            StageName::Quantize => {
+                QuantizeFields::deserialize(fields)?;
+                Stage::Quantize
+            },
+            StageName::Scatter => {
+                let ScatterFields { feature, biomes, density } = ScatterFields::deserialize(fields)?;
+                Stage::Scatter { feature, biomes, density }
+            },
+        })
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Stage {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(StageVisitor)
+    }
+}
+
+fn default_contrast() -> f64 {
+    HeightmapSettings::default().contrast
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Everything a pipeline produces. Heights are in world units, the other maps are only present once their stage has run.
+#[derive(Debug, Clone)]
+pub struct World {
+    pub layout: Layout,
+    pub heights: HexMap<f32>,
+    pub climate: Option<HexMap<Climate>>,
+    pub biomes: Option<HexMap<Biome>>,
+    pub water: Option<HexMap<Hydrology>>,
+    pub features: HexMap<Option<String>>,
+}
+
+/// An ordered list of generation stages read from a TOML file, along with the settings they share
+#[derive(Debug, Clone, PartialEq)]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Pipeline {
+    #[serde(deserialize_with = "deserialize_shape")]
+    pub shape: MapShape,
+    /// Distance from a hex's center to its corners in world units
+    #[serde(default = "default_hex_size")]
+    pub hex_size: f32,
+    #[serde(default = "default_levels")]
+    pub levels: u32,
+    #[serde(default = "default_step_height")]
+    pub step_height: f32,
+    /// World space height of the sea, shared by every stage that cares about water
+    #[serde(default = "default_sea_level")]
+    pub sea_level: f32,
+    #[serde(default)]
+    pub stages: Vec<Stage>,
+}
+
+fn check_shape(shape: MapShape) -> anyhow::Result<MapShape> {
+    match shape.hex_count() {
+        Some(_) => Ok(shape),
+        None => anyhow::bail!("invalid map shape {shape:?}, sizes must not be negative and minimums must not exceed maximums"),
+    }
+}
+
+/// Rejects shapes `HexMap` cannot hold while parsing, so the error points at `shape` rather than surfacing in `run`
+fn deserialize_shape<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<MapShape, D::Error> {
+    use serde::Deserialize;
+
+    check_shape(MapShape::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+}
+
+fn default_hex_size() -> f32 {
+    1.0
+}
+
+fn default_levels() -> u32 {
+    HeightmapSettings::default().levels
+}
+
+fn default_step_height() -> f32 {
+    HeightmapSettings::default().step_height
+}
+
+fn default_sea_level() -> f32 {
+    BiomeTable::default().sea_level
+}
+
+impl std::str::FromStr for Pipeline {
+    type Err = toml::de::Error;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        toml::from_str(source)
+    }
+}
+
+impl basalt_resource::Resource for Pipeline {
+    type Context = ();
+
+    fn load(file_name: &str, _: &()) -> anyhow::Result<Self> {
+        Pipeline::load(file_name)
+    }
+}
+
+impl Pipeline {
+    /// Loads a pipeline from the assets folder. Parse errors name the offending key and its line.
+    pub fn load(file_name: &str) -> anyhow::Result<Self> {
+        let source = basalt_resource::load_string(file_name)
+            .with_context(|| format!("failed to read pipeline `{file_name}`"))?;
+        source.parse()
+            .with_context(|| format!("invalid pipeline `{file_name}`"))
+    }
+
+    pub fn layout(&self) -> Layout {
+        Layout::pointy(self.hex_size)
+    }
+
+    fn heightmap_settings(&self, fbm: Fbm, contrast: f64) -> HeightmapSettings {
+        HeightmapSettings { fbm, layout: self.layout(), levels: self.levels, step_height: self.step_height, contrast }
+    }
+
+    /// Height of the top elevation level, used to move between world heights and the normalized heights masks work on
+    pub fn max_height(&self) -> f32 {
+        (self.levels.max(1) as f32 * self.step_height).max(f32::EPSILON)
+    }
+
+    /// Runs every stage in order on a flat map at height zero
+    pub fn run(&self, context: &GenContext) -> anyhow::Result<World> {
+        check_shape(self.shape)?;
+        let mut world = World {
+            layout: self.layout(),
+            heights: HexMap::filled(self.shape, 0.0),
+            climate: None,
+            biomes: None,
+            water: None,
+            features: HexMap::filled(self.shape, None),
+        };
+
+        for (index, stage) in self.stages.iter().enumerate() {
+            self.run_stage(context, stage, &mut world)
+                .with_context(|| format!("stage {} ({}) failed", index + 1, stage.name()))?;
+        }
+
+        Ok(world)
+    }
+
+    fn run_stage(&self, context: &GenContext, stage: &Stage, world: &mut World) -> anyhow::Result<()> {
+        match stage {
+            Stage::Noise { fbm, contrast } => {
+                let settings = self.heightmap_settings(*fbm, *contrast);
+                let heights = heightmap::sample_heights(context, &world.heights, &settings);
+                world.heights = heights.map_values(|_, &h| settings.raw_height(h));
+            },
+            Stage::Mask { mask, blend } => {
+                let max_height = self.max_height();
+                let mut heights = world.heights.map_values(|_, &h| (h / max_height) as f64);
+                mask.build()?.apply(context, &mut heights, &world.layout, *blend);
+                world.heights = heights.map_values(|_, &h| h as f32 * max_height);
+            },
+            Stage::Erosion(settings) => {
+                erosion::erode(&mut world.heights, &ErosionSettings { sea_level: self.sea_level, ..*settings });
+            },
+            Stage::Rivers(settings) => {
+                let settings = RiverSettings { sea_level: self.sea_level, ..*settings };
+                world.water = Some(rivers::hydrology(&world.heights, world.climate.as_ref(), &settings));
+            },
+            Stage::Biome { climate, table, shoreline } => {
+                let settings = ClimateSettings { layout: world.layout, sea_level: self.sea_level, ..*climate };
+                let climate = biome::climate(context, &world.heights, &settings);
+                let table = BiomeTable { sea_level: self.sea_level, ..table.clone() };
+                let mut biomes = biome::classify(&world.heights, &climate, &table);
+                if *shoreline {
+                    biome::apply_shoreline(&mut biomes, &mask::shoreline(&world.heights, self.sea_level));
+                }
+                world.climate = Some(climate);
+                world.biomes = Some(biomes);
+            },
+            Stage::Quantize => {
+                let settings = self.heightmap_settings(Fbm::default(), default_contrast());
+                for height in world.heights.values_mut() {
+                    *height = settings.quantize(*height);
+                }
+            },
+            Stage::Scatter { feature, biomes, density } => {
+                let tile_biomes = world.biomes.as_ref()
+                    .with_context(|| format!("scatter `{feature}` needs a biome stage before it"))?;
+
+                // Every tile draws a number, so changing the biome list does not shift where other tiles land
+                let mut rng = context.stage_rng(&format!("scatter.{feature}"));
+                for (hex, slot) in world.features.iter_mut() {
+                    let roll = rng.gen::<f64>();
+                    let wet = world.water.as_ref().is_some_and(|w| w[hex].is_lake() || w[hex].is_river());
+                    if slot.is_none() && !wet && roll < *density && biomes.contains(&tile_biomes[hex]) {
+                        *slot = Some(feature.clone());
+                    }
+                }
+            },
+        }
+        Ok(())
+    }
+}
+
+impl Stage {
+    /// The name the stage is written as in a pipeline file
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::Noise { .. } => "noise",
+            Stage::Mask { .. } => "mask",
+            Stage::Erosion(_) => "erosion",
+            Stage::Rivers(_) => "rivers",
+            Stage::Biome { .. } => "biome",
+            Stage::Quantize => "quantize",
+            Stage::Scatter { .. } => "scatter",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(source: &str) -> String {
+        source.parse::<Pipeline>().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn default_pipeline_parses() {
+        let source = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/../../assets/default_world.toml")).unwrap();
+        let pipeline = source.parse::<Pipeline>().unwrap();
+        assert_eq!(pipeline.stages.len(), 7);
+        assert!(matches!(pipeline.stages[4], Stage::Biome { shoreline: true, .. }));
+        assert_eq!(toml::to_string(&pipeline).unwrap().parse::<Pipeline>().unwrap(), pipeline);
+    }
+
+    #[test]
+    fn unknown_stage_key_reports_its_line() {
+        let error = parse_error("shape = { Triangle = { size = 3 } }\n\n[[stages]]\nstage = \"erosion\"\niterashuns = 4\n");
+        assert!(error.contains("line 5"), "{error}");
+        assert!(error.contains("iterashuns"), "{error}");
+    }
+
+    #[test]
+    fn bad_value_in_later_stage_reports_its_line() {
+        let source = "shape = { Triangle = { size = 3 } }\n\n[[stages]]\nstage = \"quantize\"\n\n[[stages]]\nstage = \"noise\"\nfbm = { octaves = \"five\" }\n";
+        let error = parse_error(source);
+        assert!(error.contains("line 8"), "{error}");
+    }
+
+    #[test]
+    fn unknown_stage_name_lists_the_stages() {
+        let error = parse_error("shape = { Triangle = { size = 3 } }\n[[stages]]\nstage = \"erode\"\n");
+        assert!(error.contains("line 3"), "{error}");
+        assert!(error.contains("erosion"), "{error}");
+    }
+
+    #[test]
+    fn stage_keys_can_come_in_any_order() {
+        let source = "shape = { Triangle = { size = 3 } }\n[[stages]]\ncontrast = 1.5\nstage = \"noise\"\nfbm = { octaves = 2 }\n";
+        let pipeline = source.parse::<Pipeline>().unwrap();
+        assert!(matches!(pipeline.stages[0], Stage::Noise { contrast: 1.5, fbm: Fbm { octaves: 2, .. } }));
+
+        let inline = "shape = { Triangle = { size = 3 } }\nstages = [{ blend = \"multiply\", mask = { radial = { center = [0.0, 0.0], radius = 4.0, falloff = 2.0 } }, stage = \"mask\" }]\n";
+        assert!(matches!(inline.parse::<Pipeline>().unwrap().stages[0], Stage::Mask { .. }));
+
+        let error = parse_error("shape = { Triangle = { size = 3 } }\n[[stages]]\niterashuns = 4\nstage = \"erosion\"\n");
+        assert!(error.contains("iterashuns"), "{error}");
+        let error = parse_error("shape = { Triangle = { size = 3 } }\n[[stages]]\ncontrast = 1.0\n");
+        assert!(error.contains("missing field `stage`"), "{error}");
+    }
+
+    #[test]
+    fn invalid_shape_reports_its_line() {
+        let error = parse_error("hex_size = 2.0\nshape = { Hexagon = { radius = -1 } }\n");
+        assert!(error.contains("line 2"), "{error}");
+        assert!(error.contains("invalid map shape"), "{error}");
+
+        let mut pipeline = "shape = { Triangle = { size = 3 } }".parse::<Pipeline>().unwrap();
+        pipeline.shape = MapShape::Rectangle { width: 4, height: -4 };
+        assert!(pipeline.run(&GenContext::new(1)).is_err());
+    }
+
+    #[test]
+    fn unit_stage_rejects_fields() {
+        let error = parse_error("shape = { Triangle = { size = 3 } }\n[[stages]]\nstage = \"quantize\"\nlevels = 3\n");
+        assert!(error.contains("line 4"), "{error}");
+    }
+}
@@ -0,0 +1,39 @@
+use hex::{layout::Layout, map::HexMap};
+use image::{ImageBuffer, Pixel};
+
+/// Rasterizes a map top down at `scale` pixels per world unit, with X to the right and Z down the image.
+/// Each pixel takes the color of the hex under its center, pixels outside the map are set to `background`.
+pub fn rasterize<T, P, F>(map: &HexMap<T>, layout: &Layout, scale: f32, background: P, mut color: F) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel,
+    F: FnMut(&T) -> P,
+{
+    if map.is_empty() {
+        return ImageBuffer::from_pixel(1, 1, background);
+    }
+
+    let (mut min_x, mut min_z) = (f32::INFINITY, f32::INFINITY);
+    let (mut max_x, mut max_z) = (f32::NEG_INFINITY, f32::NEG_INFINITY);
+    for hex in map.coords() {
+        for (x, z) in layout.corners(hex) {
+            min_x = min_x.min(x);
+            min_z = min_z.min(z);
+            max_x = max_x.max(x);
+            max_z = max_z.max(z);
+        }
+    }
+
+    let width = ((max_x - min_x) * scale).ceil().max(1.0) as u32;
+    let height = ((max_z - min_z) * scale).ceil().max(1.0) as u32;
+    ImageBuffer::from_fn(width, height, |px, py| {
+        let x = min_x + (px as f32 + 0.5) / scale;
+        let z = min_z + (py as f32 + 0.5) / scale;
+        map.get(layout.world_to_hex(x, z).round()).map_or(background, &mut color)
+    })
+}
+
+/// Converts a color with channels in `0.0..=1.0` to an opaque pixel
+pub fn to_rgba(color: [f32; 3]) -> image::Rgba<u8> {
+    let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    image::Rgba([channel(color[0]), channel(color[1]), channel(color[2]), 255])
+}
@@ -0,0 +1,285 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet};
+
+use hex::{hexagon::Axial, map::HexMap};
+
+use crate::biome::Climate;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RiverSettings {
+    #[serde(skip)]
+    pub sea_level: f32,
+    /// Water added to every tile, scaled by the tile's moisture when a climate map is available
+    pub rainfall: f32,
+    /// Accumulated flow a land tile needs before it carries a river
+    pub river_threshold: f32,
+    /// Width of a river carrying exactly `river_threshold` flow, wider rivers grow with the square root of their flow
+    pub width_scale: f32,
+}
+
+impl Default for RiverSettings {
+    fn default() -> Self {
+        RiverSettings { sea_level: 0.5, rainfall: 1.0, river_threshold: 40.0, width_scale: 0.1 }
+    }
+}
+
+/// Water data for a single tile
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Hydrology {
+    /// Total water passing through the tile
+    pub flow: f32,
+    /// The neighbor water drains into, `None` for sea tiles and tiles that drain off the edge of the map
+    pub downstream: Option<Axial<i32>>,
+    /// How far below the spill level of its depression the tile sits, zero outside of lakes
+    pub lake_depth: f32,
+    /// Zero when the tile has no river
+    pub river_width: f32,
+}
+
+impl Hydrology {
+    #[inline]
+    pub fn is_lake(&self) -> bool {
+        self.lake_depth > 0.0
+    }
+
+    #[inline]
+    pub fn is_river(&self) -> bool {
+        self.river_width > 0.0
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Flood {
+    level: f32,
+    order: usize,
+    hex: Axial<i32>,
+}
+
+impl PartialEq for Flood {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Flood {}
+
+impl Ord for Flood {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed for a min heap, ties go to whichever tile was queued first
+        other.level.total_cmp(&self.level).then_with(|| other.order.cmp(&self.order))
+    }
+}
+
+impl PartialOrd for Flood {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Routes rain water across the heightmap. Depressions are flooded up to their spill level to form lakes
+/// (priority flood from the sea and the map edge), so every tile drains to the sea or off the map.
+/// Flow is accumulated along the drainage tree and land tiles with enough flow become rivers.
+pub fn hydrology(heights: &HexMap<f32>, climate: Option<&HexMap<Climate>>, settings: &RiverSettings) -> HexMap<Hydrology> {
+    let mut water = heights.map_values(|_, _| Hydrology::default());
+    let mut filled = heights.map_values(|_, &h| h);
+    let mut visited = HashSet::new();
+    let mut queue = BinaryHeap::new();
+    let mut order = 0;
+
+    for (hex, &height) in heights.iter() {
+        let on_edge = hex.neighbors().iter().any(|&n| !heights.contains(n));
+        if height < settings.sea_level || on_edge {
+            visited.insert(hex);
+            queue.push(Flood { level: height, order, hex });
+            order += 1;
+        }
+    }
+
+    // Tiles in the order the flood reached them, every tile comes after the tile it drains into
+    let mut drained = Vec::with_capacity(heights.len());
+    while let Some(Flood { level, hex, .. }) = queue.pop() {
+        drained.push(hex);
+        for next in hex.neighbors() {
+            if !heights.contains(next) || !visited.insert(next) {
+                continue;
+            }
+
+            let next_level = heights[next].max(level);
+            filled[next] = next_level;
+            water[next].downstream = Some(hex);
+            queue.push(Flood { level: next_level, order, hex: next });
+            order += 1;
+        }
+    }
+
+    for &hex in drained.iter().rev() {
+        let moisture = climate.map_or(1.0, |c| c[hex].moisture as f32);
+        water[hex].flow += settings.rainfall * moisture;
+        water[hex].lake_depth = filled[hex] - heights[hex];
+
+        if let Some(next) = water[hex].downstream {
+            let flow = water[hex].flow;
+            water[next].flow += flow;
+        }
+    }
+
+    for (hex, tile) in water.iter_mut() {
+        let is_land = heights[hex] >= settings.sea_level;
+        if is_land && !tile.is_lake() && tile.flow >= settings.river_threshold {
+            tile.river_width = settings.width_scale * (tile.flow / settings.river_threshold).sqrt();
+        }
+    }
+
+    water
+}
+
+/// Traces every river from its source down to where it ends in the sea, a lake or the edge of the map.
+/// Each river lists its tiles in downstream order, including the tile it flows into last.
+/// Tributaries stop at the tile where they join a larger river.
+pub fn rivers(water: &HexMap<Hydrology>) -> Vec<Vec<Axial<i32>>> {
+    let fed = water.iter()
+        .filter(|(_, tile)| tile.is_river())
+        .filter_map(|(_, tile)| tile.downstream)
+        .collect::<HashSet<_>>();
+
+    let mut joined = HashSet::new();
+    let mut rivers = Vec::new();
+
+    // Sources are traced in coordinate order, the first river to reach a tile continues past it and later ones stop there
+    let sources = water.iter().filter(|(hex, tile)| tile.is_river() && !fed.contains(hex)).map(|(hex, _)| hex);
+
+    for source in sources.collect::<Vec<_>>() {
+        let mut river = vec![source];
+        let mut current = source;
+        while let Some(next) = water[current].downstream {
+            river.push(next);
+            if !water[next].is_river() || !joined.insert(next) {
+                break;
+            }
+            current = next;
+        }
+        joined.insert(source);
+        rivers.push(river);
+    }
+
+    rivers
+}
+
+#[cfg(test)]
+mod tests {
+    use hex::map::MapShape;
+
+    use super::*;
+
+    fn origin() -> Axial<i32> {
+        Axial::new(0, 0)
+    }
+
+    /// A bowl ringed by a wall at height 2 with a single notch at 1.5, surrounded by sea
+    fn basin() -> HexMap<f32> {
+        let notch = Axial::new(2, 0);
+        HexMap::from_fn(MapShape::Hexagon { radius: 3 }, |hex| match origin().distance(&hex) {
+            0 => 1.0,
+            1 => 1.2,
+            2 if hex == notch => 1.5,
+            2 => 2.0,
+            _ => 0.2,
+        })
+    }
+
+    /// A lake at the bottom of a crater whose inner slopes drain into it, emptying through a channel to the sea
+    fn crater() -> HexMap<f32> {
+        HexMap::from_fn(MapShape::Hexagon { radius: 5 }, |hex| {
+            let channel = hex.r() == 0 && hex.q() > 1;
+            match origin().distance(&hex) {
+                5 => 0.0,
+                distance if channel => 1.7 - 0.1 * distance as f32,
+                0 | 1 => 1.0,
+                distance => distance as f32,
+            }
+        })
+    }
+
+    /// Land sloping down towards negative `q`, surrounded by a ring of sea
+    fn coast() -> HexMap<f32> {
+        HexMap::from_fn(MapShape::Hexagon { radius: 6 }, |hex| {
+            if origin().distance(&hex) == 6 { 0.0 } else { 1.0 + 0.1 * hex.q() as f32 + 0.01 * hex.r() as f32 }
+        })
+    }
+
+    fn settings() -> RiverSettings {
+        RiverSettings { sea_level: 0.5, rainfall: 1.0, river_threshold: 2.5, width_scale: 0.1 }
+    }
+
+    #[test]
+    fn closed_basin_fills_to_its_spill_height() {
+        let heights = basin();
+        let water = hydrology(&heights, None, &settings());
+
+        for (hex, tile) in water.iter() {
+            let expected = if origin().distance(&hex) < 2 { 1.5 - heights[hex] } else { 0.0 };
+            assert!((tile.lake_depth - expected).abs() < 1e-6, "{hex:?} is {} deep, expected {expected}", tile.lake_depth);
+            assert!(!(tile.is_lake() && tile.is_river()), "{hex:?}");
+        }
+        assert!(water[origin()].is_lake());
+
+        // The lake overflows through the notch
+        let mut current = origin();
+        while let Some(next) = water[current].downstream {
+            current = next;
+            if origin().distance(&current) == 2 {
+                assert_eq!(current, Axial::new(2, 0));
+            }
+        }
+        assert!(heights[current] < settings().sea_level);
+    }
+
+    #[test]
+    fn flow_grows_downstream() {
+        for heights in [basin(), crater(), coast()] {
+            let settings = settings();
+            let water = hydrology(&heights, None, &settings);
+            for (hex, tile) in water.iter() {
+                assert!(tile.flow >= settings.rainfall, "{hex:?}");
+                if let Some(next) = tile.downstream {
+                    assert_eq!(hex.distance(&next), 1);
+                    assert!(water[next].flow >= tile.flow + settings.rainfall, "{hex:?} -> {next:?}");
+                }
+            }
+
+            // All the rain ends up leaving the map through the tiles that drain nowhere
+            let outflow = water.values().filter(|tile| tile.downstream.is_none()).map(|tile| tile.flow).sum::<f32>();
+            assert!((outflow - heights.len() as f32 * settings.rainfall).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn rivers_end_in_the_sea_or_a_lake() {
+        let ends = |heights: &HexMap<f32>| {
+            let water = hydrology(heights, None, &settings());
+            rivers(&water).iter().map(|river| *river.last().unwrap()).map(|end| (heights[end], water[end])).collect::<Vec<_>>()
+        };
+        assert!(ends(&coast()).iter().any(|(height, _)| *height < settings().sea_level));
+        assert!(ends(&crater()).iter().any(|(_, tile)| tile.is_lake()));
+
+        for heights in [basin(), crater(), coast()] {
+            let settings = settings();
+            let water = hydrology(&heights, None, &settings);
+            let traced = rivers(&water);
+            assert!(!traced.is_empty());
+
+            for river in &traced {
+                assert!(river.len() >= 2, "{river:?}");
+                assert!(river.windows(2).all(|pair| water[pair[0]].downstream == Some(pair[1])), "{river:?}");
+                assert!(river[..river.len() - 1].iter().all(|hex| water[*hex].is_river()), "{river:?}");
+
+                let end = *river.last().unwrap();
+                // Tributaries end where they join a river that carries on to the sea or a lake
+                let joins = traced.iter().any(|other| other != river && other[..other.len() - 1].contains(&end));
+                assert!(heights[end] < settings.sea_level || water[end].is_lake() || joins, "{river:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,465 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use hex::{hexagon::{Axial, Direction}, map::{HexMap, MapShape}};
+use rand::Rng;
+
+use crate::context::GenContext;
+
+pub const STAGE: &str = "wfc";
+
+/// A tile that can be placed by the solver. `sockets` lists the socket on each edge in `Direction` order,
+/// and two tiles may sit next to each other when the sockets on their shared edge are equal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileDef {
+    pub id: u32,
+    pub sockets: [u32; 6],
+    /// Relative frequency of the tile, shared between its rotations
+    pub weight: f32,
+    /// Rotations the tile may be placed with, in 60 degree counter-clockwise steps. Empty means only unrotated.
+    pub rotations: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Tileset {
+    pub tiles: Vec<TileDef>,
+}
+
+/// A tile placed by the solver
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Placement {
+    pub id: u32,
+    pub rotation: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WfcError {
+    /// The tileset has no placeable tiles
+    EmptyTileset,
+    /// A fixed cell names a tile or rotation the tileset does not allow, or lies outside the map
+    InvalidFixedCell(Axial<i32>),
+    /// The fixed cells cannot all be satisfied together
+    Contradiction,
+    /// The solver gave up after undoing this many choices
+    BacktrackLimit(usize),
+}
+
+impl fmt::Display for WfcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WfcError::EmptyTileset => write!(f, "tileset has no placeable tiles"),
+            WfcError::InvalidFixedCell(hex) => write!(f, "fixed cell at ({}, {}) is outside the map or not in the tileset", hex.q(), hex.r()),
+            WfcError::Contradiction => write!(f, "fixed cells cannot be satisfied together"),
+            WfcError::BacktrackLimit(n) => write!(f, "no solution found after backtracking {n} times"),
+        }
+    }
+}
+
+impl std::error::Error for WfcError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WfcSettings {
+    pub shape: MapShape,
+    /// Cells placed before solving, a `None` rotation allows any of the tile's rotations
+    pub fixed: Vec<(Axial<i32>, u32, Option<u8>)>,
+    pub max_backtracks: usize,
+}
+
+struct Variant {
+    placement: Placement,
+    sockets: [u32; 6],
+    weight: f64,
+}
+
+/// A set of variant indices
+#[derive(Debug, Clone, PartialEq)]
+struct Domain(Vec<u64>);
+
+/// Variants removed from one word of a cell's domain, recorded so a choice can be undone
+struct Removal {
+    hex: Axial<i32>,
+    word: usize,
+    bits: u64,
+}
+
+impl Domain {
+    fn empty(size: usize) -> Self {
+        Domain(vec![0; size.div_ceil(64)])
+    }
+
+    fn full(size: usize) -> Self {
+        let mut domain = Self::empty(size);
+        (0..size).for_each(|i| domain.insert(i));
+        domain
+    }
+
+    fn insert(&mut self, i: usize) {
+        self.0[i / 64] |= 1 << (i % 64);
+    }
+
+    fn remove(&mut self, i: usize) {
+        self.0[i / 64] &= !(1 << (i % 64));
+    }
+
+    fn len(&self) -> usize {
+        self.0.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.0.iter().enumerate().flat_map(|(w, &bits)| {
+            (0..64).filter(move |b| bits & (1 << b) != 0).map(move |b| w * 64 + b)
+        })
+    }
+
+    fn union_with(&mut self, other: &Domain) {
+        self.0.iter_mut().zip(&other.0).for_each(|(a, b)| *a |= b);
+    }
+
+    /// Intersects in place, returning true if anything was removed
+    fn intersect_with(&mut self, other: &Domain) -> bool {
+        let mut changed = false;
+        for (a, b) in self.0.iter_mut().zip(&other.0) {
+            let next = *a & b;
+            changed |= next != *a;
+            *a = next;
+        }
+        changed
+    }
+
+    /// Intersects in place like `intersect_with`, pushing whatever is removed from `hex` onto `trail`
+    fn restrict(&mut self, other: &Domain, hex: Axial<i32>, trail: &mut Vec<Removal>) -> bool {
+        let mut changed = false;
+        for (word, (a, b)) in self.0.iter_mut().zip(&other.0).enumerate() {
+            let bits = *a & !b;
+            if bits != 0 {
+                *a &= b;
+                trail.push(Removal { hex, word, bits });
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+/// Puts back everything removed since the trail was `mark` entries long
+fn undo(domains: &mut HexMap<Domain>, trail: &mut Vec<Removal>, mark: usize) {
+    for removal in trail.drain(mark..).rev() {
+        domains[removal.hex].0[removal.word] |= removal.bits;
+    }
+}
+
+/// Natural logarithm from IEEE add, multiply and divide only, so entropies compare the same on every platform.
+/// `x` is split into `m * 2^e` with `m` in `1.0..2.0` and `ln(m)` summed from the `atanh` series.
+fn ln(x: f64) -> f64 {
+    const LN_2: f64 = std::f64::consts::LN_2;
+    // Subnormals are scaled into the normal range first so the exponent bits are meaningful
+    let (x, bias) = if x < f64::MIN_POSITIVE { (x * (1u64 << 54) as f64, -54) } else { (x, 0) };
+
+    let bits = x.to_bits();
+    let exponent = ((bits >> 52) & 0x7ff) as i64 - 1023 + bias;
+    let mantissa = f64::from_bits((bits & ((1 << 52) - 1)) | (1023 << 52));
+
+    let s = (mantissa - 1.0) / (mantissa + 1.0);
+    let s2 = s * s;
+    let mut term = s;
+    let mut sum = 0.0;
+    for k in 0..20 {
+        sum += term / (2 * k + 1) as f64;
+        term *= s2;
+    }
+    exponent as f64 * LN_2 + 2.0 * sum
+}
+
+struct Solver {
+    variants: Vec<Variant>,
+    /// For every variant and direction, the variants allowed in the neighbor on that side
+    compatible: Vec<[Domain; 6]>,
+}
+
+impl Solver {
+    fn new(tileset: &Tileset) -> Self {
+        let mut variants = Vec::new();
+        for tile in &tileset.tiles {
+            let mut rotations = if tile.rotations.is_empty() { vec![0] } else { tile.rotations.iter().map(|r| r % 6).collect() };
+            rotations.sort_unstable();
+            rotations.dedup();
+
+            for &rotation in &rotations {
+                // Rotating counter-clockwise moves the socket facing direction i to face direction i + rotation
+                let sockets = std::array::from_fn(|d| tile.sockets[(d + 6 - rotation as usize) % 6]);
+                variants.push(Variant {
+                    placement: Placement { id: tile.id, rotation },
+                    sockets,
+                    weight: tile.weight.max(0.0) as f64 / rotations.len() as f64,
+                });
+            }
+        }
+
+        let compatible = variants.iter().map(|a| {
+            std::array::from_fn(|d| {
+                let opposite = Direction::from_index(d).opposite().index();
+                let mut domain = Domain::empty(variants.len());
+                for (j, b) in variants.iter().enumerate() {
+                    if a.sockets[d] == b.sockets[opposite] {
+                        domain.insert(j);
+                    }
+                }
+                domain
+            })
+        }).collect();
+
+        Solver { variants, compatible }
+    }
+
+    /// Removes unsupported variants outwards from `changed` until nothing changes, returning false on a contradiction.
+    /// Every removal is recorded in `trail`.
+    fn propagate(&self, domains: &mut HexMap<Domain>, changed: impl IntoIterator<Item = Axial<i32>>, trail: &mut Vec<Removal>) -> bool {
+        let mut queue = changed.into_iter().collect::<VecDeque<_>>();
+        while let Some(hex) = queue.pop_front() {
+            for direction in Direction::ALL {
+                let next = hex.neighbor(direction);
+                if !domains.contains(next) {
+                    continue;
+                }
+
+                let mut allowed = Domain::empty(self.variants.len());
+                for v in domains[hex].iter() {
+                    allowed.union_with(&self.compatible[v][direction.index()]);
+                }
+
+                if domains[next].restrict(&allowed, next, trail) {
+                    if domains[next].len() == 0 {
+                        return false;
+                    }
+                    queue.push_back(next);
+                }
+            }
+        }
+        true
+    }
+
+    /// The undecided cell with the lowest weighted entropy, ties going to the first in coordinate order
+    fn lowest_entropy(&self, domains: &HexMap<Domain>) -> Option<Axial<i32>> {
+        let mut best: Option<(f64, Axial<i32>)> = None;
+        for (hex, domain) in domains.iter() {
+            if domain.len() <= 1 {
+                continue;
+            }
+
+            let total: f64 = domain.iter().map(|v| self.variants[v].weight).sum();
+            let entropy = if total > 0.0 {
+                ln(total) - domain.iter().map(|v| self.variants[v].weight).filter(|&w| w > 0.0).map(|w| w * ln(w)).sum::<f64>() / total
+            } else {
+                0.0
+            };
+
+            if best.is_none_or(|(e, _)| entropy < e) {
+                best = Some((entropy, hex));
+            }
+        }
+        best.map(|(_, hex)| hex)
+    }
+
+    fn choose<R: Rng>(&self, domain: &Domain, rng: &mut R) -> usize {
+        let options = domain.iter().collect::<Vec<_>>();
+        let total: f64 = options.iter().map(|&v| self.variants[v].weight).sum();
+        if total <= 0.0 {
+            return options[rng.gen_range(0..options.len() as u32) as usize];
+        }
+
+        let mut pick = rng.gen::<f64>() * total;
+        for &v in &options {
+            pick -= self.variants[v].weight;
+            if pick < 0.0 {
+                return v;
+            }
+        }
+        *options.last().expect("Domain is not empty")
+    }
+}
+
+/// Fills `settings.shape` with tiles so every pair of neighbors agrees on their shared sockets.
+/// Cells are collapsed lowest entropy first with choices drawn from the context's wfc stage, and a contradiction
+/// undoes the most recent choice and rules it out.
+pub fn solve(context: &GenContext, tileset: &Tileset, settings: &WfcSettings) -> Result<HexMap<Placement>, WfcError> {
+    let solver = Solver::new(tileset);
+    if solver.variants.is_empty() {
+        return Err(WfcError::EmptyTileset);
+    }
+
+    let count = solver.variants.len();
+    let mut domains = HexMap::filled(settings.shape, Domain::full(count));
+
+    for &(hex, id, rotation) in &settings.fixed {
+        let mut fixed = Domain::empty(count);
+        for (i, variant) in solver.variants.iter().enumerate() {
+            if variant.placement.id == id && rotation.is_none_or(|r| r % 6 == variant.placement.rotation) {
+                fixed.insert(i);
+            }
+        }
+
+        let domain = domains.get_mut(hex).ok_or(WfcError::InvalidFixedCell(hex))?;
+        if fixed.len() == 0 {
+            return Err(WfcError::InvalidFixedCell(hex));
+        }
+
+        domain.intersect_with(&fixed);
+        if domain.len() == 0 {
+            return Err(WfcError::Contradiction);
+        }
+    }
+
+    // Removals before the first choice come from the fixed cells and are never undone
+    let mut trail = Vec::new();
+    if !solver.propagate(&mut domains, settings.fixed.iter().map(|f| f.0), &mut trail) {
+        return Err(WfcError::Contradiction);
+    }
+
+    let mut rng = context.stage_rng(STAGE);
+    // Each choice remembers how long the trail was before it was made
+    let mut decisions: Vec<(usize, Axial<i32>, usize)> = Vec::new();
+    let mut backtracks = 0;
+
+    while let Some(hex) = solver.lowest_entropy(&domains) {
+        let choice = solver.choose(&domains[hex], &mut rng);
+        decisions.push((trail.len(), hex, choice));
+
+        let mut collapsed = Domain::empty(count);
+        collapsed.insert(choice);
+        domains[hex].restrict(&collapsed, hex, &mut trail);
+
+        if solver.propagate(&mut domains, [hex], &mut trail) {
+            continue;
+        }
+
+        // Undo choices until ruling one out leaves a consistent map. Ruling out is recorded under the choice
+        // before it, so backtracking further also restores the ruled out variant.
+        loop {
+            backtracks += 1;
+            if backtracks > settings.max_backtracks {
+                return Err(WfcError::BacktrackLimit(settings.max_backtracks));
+            }
+
+            let Some((mark, hex, choice)) = decisions.pop() else {
+                return Err(WfcError::Contradiction);
+            };
+            undo(&mut domains, &mut trail, mark);
+
+            let mut without = Domain::full(count);
+            without.remove(choice);
+            domains[hex].restrict(&without, hex, &mut trail);
+            if domains[hex].len() > 0 && solver.propagate(&mut domains, [hex], &mut trail) {
+                break;
+            }
+        }
+    }
+
+    Ok(domains.map_values(|_, domain| {
+        let v = domain.iter().next().expect("Every cell is collapsed");
+        solver.variants[v].placement
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tile(id: u32, sockets: [u32; 6], weight: f32, rotations: Vec<u8>) -> TileDef {
+        TileDef { id, sockets, weight, rotations }
+    }
+
+    /// Land, sea and coast pieces, where coasts need rotating to fit
+    fn coast_tileset() -> Tileset {
+        Tileset {
+            tiles: vec![
+                tile(0, [0; 6], 3.0, vec![]),
+                tile(1, [1; 6], 2.0, vec![]),
+                tile(2, [0, 0, 0, 1, 1, 1], 1.0, (0..6).collect()),
+            ],
+        }
+    }
+
+    fn sockets(tileset: &Tileset, placement: Placement) -> [u32; 6] {
+        let tile = tileset.tiles.iter().find(|t| t.id == placement.id).unwrap();
+        std::array::from_fn(|d| tile.sockets[(d + 6 - placement.rotation as usize) % 6])
+    }
+
+    fn assert_consistent(tileset: &Tileset, map: &HexMap<Placement>) {
+        for (hex, &placement) in map.iter() {
+            for direction in Direction::ALL {
+                if let Some(&other) = map.get(hex.neighbor(direction)) {
+                    let opposite = direction.opposite().index();
+                    assert_eq!(sockets(tileset, placement)[direction.index()], sockets(tileset, other)[opposite], "{hex:?} {direction:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ln_matches_std() {
+        for x in [1e-300, 1e-10, 0.001, 0.5, 1.0, 1.5, 2.0, 3.0, 10.0, 12345.678, 1e200, f64::MIN_POSITIVE / 8.0] {
+            assert!((ln(x) - x.ln()).abs() <= 1e-12 * x.ln().abs().max(1.0), "ln({x}) = {} != {}", ln(x), x.ln());
+        }
+    }
+
+    #[test]
+    fn solutions_agree_on_every_edge() {
+        let tileset = coast_tileset();
+        for seed in 0..8 {
+            let settings = WfcSettings { shape: MapShape::Hexagon { radius: 4 }, fixed: vec![], max_backtracks: 1000 };
+            let map = solve(&GenContext::new(seed), &tileset, &settings).unwrap();
+            assert_consistent(&tileset, &map);
+        }
+    }
+
+    #[test]
+    fn same_seed_same_solution() {
+        let tileset = coast_tileset();
+        let settings = WfcSettings {
+            shape: MapShape::Rectangle { width: 6, height: 5 },
+            fixed: vec![(Axial::new(0, 0), 1, None)],
+            max_backtracks: 1000,
+        };
+        let a = solve(&GenContext::new(3), &tileset, &settings).unwrap();
+        let b = solve(&GenContext::new(3), &tileset, &settings).unwrap();
+        assert_eq!(a.into_values(), b.into_values());
+    }
+
+    #[test]
+    fn backtracks_out_of_dead_ends() {
+        // Nothing matches tile 1's south east edge, so the heavily weighted tile keeps being picked for cells
+        // with a south east neighbor and has to be undone
+        let tileset = Tileset {
+            tiles: vec![
+                tile(0, [0; 6], 1.0, vec![]),
+                tile(1, [0, 0, 0, 0, 0, 2], 100.0, vec![]),
+            ],
+        };
+        let settings = WfcSettings { shape: MapShape::Hexagon { radius: 3 }, fixed: vec![], max_backtracks: 10_000 };
+        let map = solve(&GenContext::new(1), &tileset, &settings).unwrap();
+        assert_consistent(&tileset, &map);
+        for (hex, placement) in map.iter() {
+            if map.contains(hex.neighbor(Direction::SouthEast)) {
+                assert_eq!(placement.id, 0, "{hex:?}");
+            }
+        }
+        assert!(map.values().any(|p| p.id == 1));
+
+        let settings = WfcSettings { max_backtracks: 0, ..settings };
+        assert_eq!(solve(&GenContext::new(1), &tileset, &settings).unwrap_err(), WfcError::BacktrackLimit(0));
+    }
+
+    #[test]
+    fn reports_unsatisfiable_fixed_cells() {
+        let tileset = Tileset { tiles: vec![tile(0, [0; 6], 1.0, vec![]), tile(1, [1; 6], 1.0, vec![])] };
+        let settings = WfcSettings {
+            shape: MapShape::Hexagon { radius: 1 },
+            fixed: vec![(Axial::new(0, 0), 0, None), (Axial::new(1, 0), 1, None)],
+            max_backtracks: 10,
+        };
+        assert_eq!(solve(&GenContext::new(0), &tileset, &settings).unwrap_err(), WfcError::Contradiction);
+
+        let settings = WfcSettings { fixed: vec![(Axial::new(5, 5), 0, None)], ..settings };
+        assert_eq!(solve(&GenContext::new(0), &tileset, &settings).unwrap_err(), WfcError::InvalidFixedCell(Axial::new(5, 5)));
+    }
+}
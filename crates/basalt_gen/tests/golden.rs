@@ -0,0 +1,90 @@
+// Golden values for generation. Any change to these hashes means the same seed now produces different terrain,
+// which breaks saved worlds and shared seeds. Update them only for intentional changes to generation.
+
+use basalt_gen::{context::GenContext, heightmap::{self, HeightmapSettings}, pipeline::{Pipeline, World}};
+use hex::map::MapShape;
+
+/// FNV-1a, kept here so the hashes do not depend on a hasher that could change between releases
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Fnv(0xCBF2_9CE4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01B3);
+        }
+    }
+
+    fn write_f32(&mut self, value: f32) {
+        self.write(&value.to_bits().to_le_bytes());
+    }
+}
+
+fn hash_world(world: &World) -> u64 {
+    let mut hash = Fnv::new();
+    for (hex, &height) in world.heights.iter() {
+        hash.write(&hex.q().to_le_bytes());
+        hash.write(&hex.r().to_le_bytes());
+        hash.write_f32(height);
+        if let Some(biomes) = &world.biomes {
+            hash.write(format!("{:?}", biomes[hex]).as_bytes());
+        }
+        if let Some(climate) = &world.climate {
+            hash.write(&climate[hex].moisture.to_bits().to_le_bytes());
+            hash.write(&climate[hex].temperature.to_bits().to_le_bytes());
+        }
+        if let Some(water) = &world.water {
+            hash.write_f32(water[hex].flow);
+            hash.write_f32(water[hex].lake_depth);
+            hash.write_f32(water[hex].river_width);
+        }
+        if let Some(feature) = &world.features[hex] {
+            hash.write(feature.as_bytes());
+        }
+    }
+    hash.0
+}
+
+fn default_pipeline() -> Pipeline {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../../assets/default_world.toml");
+    std::fs::read_to_string(path).unwrap().parse().unwrap()
+}
+
+#[test]
+fn heightmap_is_stable() {
+    let settings = HeightmapSettings::default();
+    let expected = [(0, 0x310881064b7d788), (42, 0x1ab4b23a58c4795), (u64::MAX, 0x737c7953e7437218)];
+
+    for (seed, golden) in expected {
+        let heights = heightmap::generate(&GenContext::new(seed), MapShape::Hexagon { radius: 24 }, &settings);
+        let mut hash = Fnv::new();
+        heights.values().for_each(|&h| hash.write_f32(h));
+        assert_eq!(hash.0, golden, "heightmap for seed {seed} changed: {:#x}", hash.0);
+    }
+}
+
+#[test]
+fn default_pipeline_is_stable() {
+    let mut pipeline = default_pipeline();
+    let small = pipeline.run(&GenContext::new(7)).unwrap();
+    assert_eq!(hash_world(&small), 0x797c8074215507b7, "default pipeline changed: {:#x}", hash_world(&small));
+
+    // The shipped map is tiny, so run the same stages over a larger one to cover rivers and scattering properly
+    pipeline.shape = MapShape::Hexagon { radius: 16 };
+    let large = pipeline.run(&GenContext::new(7)).unwrap();
+    assert_eq!(hash_world(&large), 0x466ea4d13c448102, "default pipeline on a large map changed: {:#x}", hash_world(&large));
+}
+
+#[test]
+fn same_seed_same_world() {
+    let pipeline = default_pipeline();
+    let a = pipeline.run(&GenContext::new(123)).unwrap();
+    let b = pipeline.run(&GenContext::new(123)).unwrap();
+    let c = pipeline.run(&GenContext::new(124)).unwrap();
+    assert_eq!(hash_world(&a), hash_world(&b));
+    assert_ne!(hash_world(&a), hash_world(&c));
+}
@@ -0,0 +1,157 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context};
+use basalt_gen::{biome::Biome, context::GenContext, pipeline::{Pipeline, World}, preview};
+use basalt_resource::AssetRoots;
+use hex::map::HexMap;
+use image::{Luma, Rgba};
+
+const USAGE: &str = "\
+Usage: basalt-gen [options]
+
+Runs a generation pipeline without a window and writes preview.png, heightmap.png and map.json.
+
+Options:
+    --seed <n>        World seed, defaults to 0
+    --config <path>   Pipeline file, defaults to default_world.toml from the asset roots
+    --assets <dir>    Asset directory searched before the default roots, can be given more than once
+    --out <dir>       Output directory, defaults to the current directory
+    --scale <px>      Pixels per world unit in the images, defaults to 16
+    -h, --help        Print this message";
+
+const WATER_COLOR: [f32; 3] = [0.2, 0.4, 0.8];
+
+struct Options {
+    seed: u64,
+    config: Option<PathBuf>,
+    assets: Vec<PathBuf>,
+    out: PathBuf,
+    scale: f32,
+}
+
+impl Options {
+    /// Returns `None` when help was requested
+    fn parse(mut args: impl Iterator<Item = String>) -> anyhow::Result<Option<Self>> {
+        let mut options = Options {
+            seed: 0,
+            config: None,
+            assets: Vec::new(),
+            out: PathBuf::from("."),
+            scale: 16.0,
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().with_context(|| format!("missing value for `{arg}`"));
+            match arg.as_str() {
+                "--seed" => options.seed = value()?.parse().context("`--seed` must be an unsigned integer")?,
+                "--config" => options.config = Some(value()?.into()),
+                "--assets" => options.assets.push(value()?.into()),
+                "--out" => options.out = value()?.into(),
+                "--scale" => options.scale = value()?.parse().context("`--scale` must be a number")?,
+                "-h" | "--help" => return Ok(None),
+                _ => bail!("unknown argument `{arg}`\n\n{USAGE}"),
+            }
+        }
+
+        if options.scale <= 0.0 {
+            bail!("`--scale` must be greater than zero");
+        }
+        Ok(Some(options))
+    }
+}
+
+/// One tile of the exported map. Fields from stages the pipeline did not run are left out.
+#[derive(serde::Serialize)]
+struct Tile {
+    height: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    biome: Option<Biome>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    moisture: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lake_depth: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    river_width: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    feature: Option<String>,
+}
+
+fn tiles(world: &World) -> HexMap<Tile> {
+    world.heights.map_values(|hex, &height| Tile {
+        height,
+        biome: world.biomes.as_ref().map(|b| b[hex]),
+        moisture: world.climate.as_ref().map(|c| c[hex].moisture),
+        temperature: world.climate.as_ref().map(|c| c[hex].temperature),
+        lake_depth: world.water.as_ref().map(|w| w[hex].lake_depth),
+        river_width: world.water.as_ref().map(|w| w[hex].river_width),
+        feature: world.features[hex].clone(),
+    })
+}
+
+fn tile_color(tile: &Tile) -> Rgba<u8> {
+    let wet = tile.lake_depth.is_some_and(|d| d > 0.0) || tile.river_width.is_some_and(|w| w > 0.0);
+    let mut color = match tile.biome {
+        _ if wet => WATER_COLOR,
+        Some(biome) => biome.appearance().color,
+        None => [0.5; 3],
+    };
+    if tile.feature.is_some() {
+        color = color.map(|c| c * 0.7);
+    }
+    preview::to_rgba(color)
+}
+
+fn run(options: &Options) -> anyhow::Result<()> {
+    // Later `--assets` directories take priority, matching how overlays stack
+    let mut roots = AssetRoots::discover();
+    for dir in &options.assets {
+        roots.push_overlay(dir);
+    }
+    basalt_resource::roots::set_roots(roots);
+
+    let pipeline = match &options.config {
+        Some(config) => {
+            let source = std::fs::read_to_string(config)
+                .with_context(|| format!("failed to read pipeline `{}`", config.display()))?;
+            source.parse()
+                .with_context(|| format!("invalid pipeline `{}`", config.display()))?
+        },
+        None => Pipeline::load("default_world.toml")?,
+    };
+
+    let world = pipeline.run(&GenContext::new(options.seed))?;
+    let tiles = tiles(&world);
+
+    std::fs::create_dir_all(&options.out)
+        .with_context(|| format!("failed to create `{}`", options.out.display()))?;
+
+    let preview = preview::rasterize(&tiles, &world.layout, options.scale, Rgba([0, 0, 0, 0]), tile_color);
+    let path = options.out.join("preview.png");
+    preview.save(&path).with_context(|| format!("failed to write `{}`", path.display()))?;
+
+    // 16 bit so that every elevation level survives, with the top level at full white
+    let max_height = pipeline.max_height();
+    let heightmap = preview::rasterize(&tiles, &world.layout, options.scale, Luma([0u16]), |tile| {
+        Luma([((tile.height / max_height).clamp(0.0, 1.0) * u16::MAX as f32).round() as u16])
+    });
+    let path = options.out.join("heightmap.png");
+    heightmap.save(&path).with_context(|| format!("failed to write `{}`", path.display()))?;
+
+    let path = options.out.join("map.json");
+    let json = serde_json::to_string(&tiles)?;
+    std::fs::write(&path, json).with_context(|| format!("failed to write `{}`", path.display()))?;
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    match Options::parse(std::env::args().skip(1))? {
+        Some(options) => run(&options),
+        None => {
+            println!("{USAGE}");
+            Ok(())
+        },
+    }
+}
@@ -0,0 +1,76 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context};
+use basalt_resource::{pack::PACK_FILE, Compression, Pack, PackBuilder};
+
+const USAGE: &str = "\
+Usage: basalt-pack [options]
+
+Packs an assets folder into a single archive for release builds.
+
+Options:
+    --assets <dir>          Folder to pack, defaults to assets
+    --out <path>            Archive to write, defaults to assets.bpak
+    --compression <kind>    zstd, lz4 or none, defaults to zstd
+    --level <n>             zstd compression level, defaults to 19
+    -h, --help              Print this message";
+
+struct Options {
+    assets: PathBuf,
+    out: PathBuf,
+    compression: Compression,
+    level: i32,
+}
+
+impl Options {
+    /// Returns `None` when help was requested
+    fn parse(mut args: impl Iterator<Item = String>) -> anyhow::Result<Option<Self>> {
+        let mut options = Options {
+            assets: PathBuf::from("assets"),
+            out: PathBuf::from(PACK_FILE),
+            compression: Compression::Zstd,
+            level: 19,
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().with_context(|| format!("missing value for `{arg}`"));
+            match arg.as_str() {
+                "--assets" => options.assets = value()?.into(),
+                "--out" => options.out = value()?.into(),
+                "--compression" => options.compression = value()?.parse()?,
+                "--level" => options.level = value()?.parse().context("`--level` must be an integer")?,
+                "-h" | "--help" => return Ok(None),
+                _ => bail!("unknown argument `{arg}`\n\n{USAGE}"),
+            }
+        }
+
+        Ok(Some(options))
+    }
+}
+
+fn run(options: &Options) -> anyhow::Result<()> {
+    let mut builder = PackBuilder::new().with_zstd_level(options.level);
+    let count = builder.add_dir(&options.assets, options.compression)?;
+    builder.write_file(&options.out)?;
+
+    // Read the archive back so a broken pack never ships
+    let pack = Pack::open(&options.out)?;
+    for entry in builder.entries() {
+        pack.read(&entry.path)?.with_context(|| format!("`{}` is missing from the written pack", entry.path))?;
+    }
+
+    let size = builder.entries().map(|e| e.size).sum::<u64>();
+    let stored = builder.entries().map(|e| e.stored_size).sum::<u64>();
+    println!("Packed {count} files from `{}` into `{}`, {size} bytes stored as {stored}", options.assets.display(), options.out.display());
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    match Options::parse(std::env::args().skip(1))? {
+        Some(options) => run(&options),
+        None => {
+            println!("{USAGE}");
+            Ok(())
+        },
+    }
+}
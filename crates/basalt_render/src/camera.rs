@@ -68,7 +68,7 @@ impl RenderCamera {
 
         info!("Initializing new Render Camera - {}", label);
 
-        let depth_texture = texture::Texture::create_depth_texture(&device, &config, &format!("{} - depth texture", label));
+        let depth_texture = texture::Texture::create_depth_texture(device, config, &format!("{} - depth texture", label));
         let camera_data = CameraData {
             eye: (0.0, 4.0, 8.0).into(),
             target: (0.0, 0.0, 0.0).into(),
@@ -130,7 +130,7 @@ impl RenderCamera {
             camera_buffer,
             camera_bind_group,
             camera_bind_group_layout,
-            clear_color: wgpu::Color { r: 0.3, g: 0.3, b: 0.3, a: 1.0 },
+            clear_color: wgpu::Color { r: 0.4, g: 0.4, b: 0.4, a: 1.0 },
             label: label.to_owned(),
         }
     }
@@ -140,8 +140,18 @@ impl RenderCamera {
         &self.depth_texture.view
     }
 
-    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+    #[inline]
+    pub fn get_clear_color(&self) -> wgpu::Color {
+        self.clear_color
+    }
+
+    /// Recreates the depth texture and updates the projection for the new aspect ratio
+    pub fn resize(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, config: &wgpu::SurfaceConfiguration) {
         self.depth_texture = texture::Texture::create_depth_texture(device, config, &format!("{} - depth_texture", self.label));
+
+        self.camera_data.aspect = config.width as f32 / config.height as f32;
+        self.camera_uniform.update_view_proj(&self.camera_data);
+        queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
     }
 
     #[inline]
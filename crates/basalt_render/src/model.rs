@@ -1,11 +1,8 @@
-use std::{io::{Cursor, BufReader}, ops::Range};
+use std::{cell::RefCell, io::{Cursor, BufReader}, ops::Range, sync::Arc};
+use basalt_resource::{AssetServer, Handle};
 use wgpu::util::DeviceExt;
 
-use crate::texture;
-
-pub fn draw_mesh<'a>(render_pass: &mut wgpu::RenderPass<'a>, mesh: &'a Mesh, material: &'a Material) {
-    draw_mesh_instanced(render_pass, mesh, material, 0..1);
-}
+use crate::texture::{self, GpuContext};
 
 pub fn draw_mesh_instanced<'a>(render_pass: &mut wgpu::RenderPass<'a>, mesh: &'a Mesh, material: &'a Material, instances: Range<u32>) {
     render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
@@ -16,10 +13,6 @@ pub fn draw_mesh_instanced<'a>(render_pass: &mut wgpu::RenderPass<'a>, mesh: &'a
     render_pass.draw_indexed(0..mesh.num_elements, 0, instances);
 }
 
-pub fn draw_model<'a>(render_pass: &mut wgpu::RenderPass<'a>, model: &'a Model) {
-    draw_model_instanced(render_pass, model, 0..1);
-}
-
 pub fn draw_model_instanced<'a>(render_pass: &mut wgpu::RenderPass<'a>, model: &'a Model, instances: Range<u32>) {
 
     for mesh in &model.meshes {
@@ -33,6 +26,8 @@ pub fn draw_model_instanced<'a>(render_pass: &mut wgpu::RenderPass<'a>, model: &
 pub struct Model {
     pub meshes: Vec<Mesh>,
     pub materials: Vec<Material>,
+    /// The MTL files the model was built from, held so the asset server keeps watching them for changes
+    pub material_libraries: Vec<Handle<String>>,
 }
 
 pub struct Mesh {
@@ -45,15 +40,26 @@ pub struct Mesh {
 
 pub struct Material {
     pub name: String,
-    pub diffuse_texture: texture::Texture,
+    pub diffuse_texture: Handle<texture::Texture>,
     pub bind_group: wgpu::BindGroup,
 }
 
+/// What loading a model needs. Material textures are loaded through `assets`, so models sharing a texture share one copy.
+#[derive(Clone)]
+pub struct ModelContext {
+    pub gpu: GpuContext,
+    pub layout: Arc<wgpu::BindGroupLayout>,
+    pub assets: AssetServer,
+}
+
 
 impl Model {
-    pub async fn from_string(obj_text: String, device: &wgpu::Device, queue: &wgpu::Queue, layout: &wgpu::BindGroupLayout) -> anyhow::Result<Model> {
+    pub async fn from_string(obj_text: String, context: &ModelContext) -> anyhow::Result<Model> {
+        let device = &context.gpu.device;
+        let layout = &context.layout;
         let obj_cursor = Cursor::new(obj_text);
         let mut obj_reader = BufReader::new(obj_cursor);
+        let material_libraries = RefCell::new(Vec::new());
 
         let (models, obj_materials) = tobj::load_obj_buf_async(
             &mut obj_reader,
@@ -62,14 +68,23 @@ impl Model {
                 single_index: true,
                 ..Default::default()
             },
-            |p| async move {
-                let mat_text = basalt_resource::load_string(&p).unwrap();
-                tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mat_text)))
+            |p| {
+                let material_libraries = &material_libraries;
+                async move {
+                    // tobj's error type cannot carry ours, so log the cause before handing back its generic one
+                    let mat_text = context.assets.load::<String>(&p, &()).map_err(|e| {
+                        log::error!("{:#}", e);
+                        tobj::LoadError::OpenFileFailed
+                    })?;
+                    let materials = tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mat_text.as_str())));
+                    material_libraries.borrow_mut().push(mat_text);
+                    materials
+                }
             }).await?;
 
             let mut materials = Vec::new();
             for m in obj_materials? {
-                let diffuse_texture = texture::Texture::from_bytes(device, queue, &basalt_resource::load_binary(&m.diffuse_texture)?, &format!("{}-diffuse", m.name))?;
+                let diffuse_texture = context.assets.load::<texture::Texture>(&m.diffuse_texture, &context.gpu)?;
                 let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
                     layout,
                     entries: &[
@@ -131,7 +146,21 @@ impl Model {
             })
             .collect::<Vec<_>>();
 
-            Ok(Model { meshes, materials })
+            Ok(Model { meshes, materials, material_libraries: material_libraries.into_inner() })
+    }
+
+    /// Whether the model has to be rebuilt when the asset at the normalized `path` changes
+    pub fn depends_on(&self, path: &str) -> bool {
+        self.material_libraries.iter().any(|m| m.path() == path) || self.materials.iter().any(|m| m.diffuse_texture.path() == path)
+    }
+}
+
+impl basalt_resource::Resource for Model {
+    type Context = ModelContext;
+
+    fn load(file_name: &str, context: &ModelContext) -> anyhow::Result<Self> {
+        // The MTL callback never actually suspends, so this resolves on the first poll
+        pollster::block_on(Self::from_string(basalt_resource::load_string(file_name)?, context))
     }
 }
 
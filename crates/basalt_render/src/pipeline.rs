@@ -1,5 +1,3 @@
-use basalt_resource;
-
 // Render Pipeline needs the following information...
 // Bind Group Layouts
 //      Camera
@@ -8,14 +6,60 @@ use basalt_resource;
 // Vertex Buffer Layout
 // Shader
 
-// pub fn create_pipeline_from_shader(shader_path: &str, label: &str ) -> wgpu::RenderPipeline {
-
-//     let shader_text = basalt_resource::load_string(shader_path).unwrap();
-//     let shader_descriptor = wgpu::ShaderModuleDescriptor {
-//         label: Some(label),
-//         source: wgpu::ShaderSource::Wgsl(shader_text.into()),
-//     };
+use crate::{model::{self, Vertex}, texture};
 
+/// Builds the pipeline that draws instanced models from WGSL source
+pub fn create_instanced_pipeline(device: &wgpu::Device, layout: &wgpu::PipelineLayout, shader_text: &str, format: wgpu::TextureFormat) -> wgpu::RenderPipeline {
+    let shader_descriptor = wgpu::ShaderModuleDescriptor {
+        label: Some("default_shader"),
+        source: wgpu::ShaderSource::Wgsl(shader_text.into()),
+    };
 
+    let shader = device.create_shader_module(shader_descriptor);
 
-// }
\ No newline at end of file
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("default_render_pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[
+                model::ModelVertex::desc(),
+                model::InstanceRaw::desc(),
+            ],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[
+                Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })
+            ],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative:false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState{
+            format: texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
@@ -1,32 +1,55 @@
+use std::{sync::Arc, time::Duration};
+
 use cgmath::Zero;
-use hex::hexagon::Axial;
+use basalt_resource::{AssetServer, Handle, Resource};
+use basalt_gen::{biome::Biome, context::GenContext, heightmap::{self, HeightmapSettings}, pipeline::{Pipeline, World}};
+use hex::map::{HexMap, MapShape};
 use wgpu::util::DeviceExt;
 use winit::window::Window;
-use log::info;
-
-use crate::{camera, model::{self, Vertex, Instance}, texture};
+use log::{error, info, warn};
+
+use crate::{camera, model::{self, Instance}, pipeline, texture};
+
+/// A small heightmap with no other stages, shown when the world pipeline cannot be loaded or run
+fn fallback_world(context: &GenContext) -> World {
+    let settings = HeightmapSettings::default();
+    let shape = MapShape::Hexagon { radius: 2 };
+    World {
+        layout: settings.layout,
+        heights: heightmap::generate(context, shape, &settings),
+        climate: None,
+        biomes: None,
+        water: None,
+        features: HexMap::filled(shape, None),
+    }
+}
 
 pub struct RenderState {
 
     surface: wgpu::Surface,
-    device: wgpu::Device,
-    queue: wgpu::Queue,
+    device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
     config: wgpu::SurfaceConfiguration,
     size: winit::dpi::PhysicalSize<u32>,
     window: Window,
 
     render_camera: camera::RenderCamera,
+    assets: AssetServer,
 
     // TEMP
-    pub test_model: model::Model,
-    pub default_pipeline: wgpu::RenderPipeline,
+    pub test_model: Handle<model::Model>,
+    default_shader: Handle<String>,
+    default_pipeline_layout: wgpu::PipelineLayout,
+    default_pipeline: Option<wgpu::RenderPipeline>,
+    model_context: model::ModelContext,
+    loading: bool,
     pub instances: Vec<Instance>,
     pub instance_buffer: wgpu::Buffer,
 }
 
 impl RenderState {
 
-    pub async fn new(window: Window) -> Self {
+    pub async fn new(window: Window, seed: u64) -> Self {
 
         let size = window.inner_size();
 
@@ -62,6 +85,7 @@ impl RenderState {
             },
             None
         ).await.unwrap();
+        let (device, queue) = (Arc::new(device), Arc::new(queue));
 
         info!("Device and Queue created successfully.");
 
@@ -112,94 +136,52 @@ impl RenderState {
                 }
             ]
         });
-        let test_model = model::Model::from_string(basalt_resource::load_string("basic_hex.obj").unwrap(), &device, &queue, &texture_bind_group_layout).await.unwrap();
+        let texture_bind_group_layout = Arc::new(texture_bind_group_layout);
+        let assets = AssetServer::new();
+        if cfg!(debug_assertions) {
+            // Lets shaders, textures and models be edited while the app is running
+            assets.watch(Duration::from_millis(500));
+        }
+        let model_context = model::ModelContext {
+            gpu: texture::GpuContext { device: device.clone(), queue: queue.clone() },
+            layout: texture_bind_group_layout.clone(),
+            assets: assets.clone(),
+        };
+        let test_model = assets.load_async::<model::Model>("basic_hex.obj", &model_context);
         // ***
 
 
         // ***
         // @TODO: Replace temp pipeline code
 
-        let default_pipeline = {
-            let default_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("default_pipeline_layout"),
-                bind_group_layouts: &[
-                    &texture_bind_group_layout,
-                    render_camera.get_bind_group_layout(),
-                ],
-                push_constant_ranges: &[],
-            });
-
-            let shader_text = basalt_resource::load_string("default_instanced.wgsl").unwrap();
-            let shader_descriptor = wgpu::ShaderModuleDescriptor {
-                label: Some("default_shader"),
-                source: wgpu::ShaderSource::Wgsl(shader_text.into()),
-            };
-
-            let shader = device.create_shader_module(shader_descriptor);
-
-            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("default_render_pipeline"),
-                layout: Some(&default_pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &shader,
-                    entry_point: "vs_main",
-                    buffers: &[
-                        model::ModelVertex::desc(),
-                        model::InstanceRaw::desc(),
-                    ],
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader,
-                    entry_point: "fs_main",
-                    targets: &[
-                        Some(wgpu::ColorTargetState {
-                            format: config.format,
-                            blend: Some(wgpu::BlendState::REPLACE),
-                            write_mask: wgpu::ColorWrites::ALL,
-                        })
-                    ],
-                }),
-                primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::TriangleList,
-                    strip_index_format: None,
-                    front_face: wgpu::FrontFace::Ccw,
-                    cull_mode: Some(wgpu::Face::Back),
-                    polygon_mode: wgpu::PolygonMode::Fill,
-                    unclipped_depth: false,
-                    conservative:false,
-                },
-                depth_stencil: Some(wgpu::DepthStencilState{
-                    format: texture::Texture::DEPTH_FORMAT,
-                    depth_write_enabled: true,
-                    depth_compare: wgpu::CompareFunction::Less,
-                    stencil: wgpu::StencilState::default(),
-                    bias: wgpu::DepthBiasState::default(),
-                }),
-                multisample: wgpu::MultisampleState {
-                    count: 1,
-                    mask: !0,
-                    alpha_to_coverage_enabled: false,
-                },
-                multiview: None,
-            })
-        };
+        let default_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("default_pipeline_layout"),
+            bind_group_layouts: &[
+                &texture_bind_group_layout,
+                render_camera.get_bind_group_layout(),
+            ],
+            push_constant_ranges: &[],
+        });
+
+        // The pipeline is built in `update` once the shader has loaded
+        let default_shader = assets.load_async::<String>("default_instanced.wgsl", &());
         // ***
 
         let instances = {
-            const SIZE: f32 = 1.0;
-
-            let positions = vec![
-                (0, 0),
-                (1, 0), (0, 1), (-1, 1), (-1, 0), (0, -1), (1, -1),
-                (2, 0), (1, 1), (0, 2), (-1, 2), (-2, 2), (-2, 1), (-2, 0), (-1, -1), (0, -2), (1, -2), (2, -2), (2, -1),
-            ];
-            let hex_positions = positions.iter().map(|p| Axial::new(p.0, p.1) ).collect::<Vec<_>>();
-
-            hex_positions.iter().map(|p| {
-                let hex_position = p.to_cartesian();
-                let position = cgmath::Vector3 { x: hex_position.0 * SIZE, y: rand::random(), z: hex_position.1 * SIZE };
+            let context = GenContext::new(seed);
+            let world = Pipeline::load("default_world.toml")
+                .and_then(|pipeline| pipeline.run(&context))
+                .unwrap_or_else(|e| {
+                    error!("Failed to generate the world, falling back to a plain heightmap: {:#}", e);
+                    fallback_world(&context)
+                });
+
+            world.heights.iter().map(|(p, height)| {
+                let hex_position = world.layout.hex_to_world(p);
+                let position = cgmath::Vector3 { x: hex_position.0, y: *height, z: hex_position.1 };
                 let rotation = cgmath::Quaternion::zero();
-                let color = cgmath::Vector3::new(0.0, 0.2, 0.1);
+                let biome = world.biomes.as_ref().map_or(Biome::Grassland, |biomes| biomes[p]);
+                let color = cgmath::Vector3::from(biome.appearance().color);
                 Instance {position, rotation, color}
             }).collect::<Vec<_>>()
         };
@@ -222,9 +204,14 @@ impl RenderState {
             window,
 
             render_camera,
+            assets,
 
             test_model,
-            default_pipeline,
+            default_shader,
+            default_pipeline_layout,
+            default_pipeline: None,
+            model_context,
+            loading: true,
             instances,
             instance_buffer,
         }
@@ -250,6 +237,11 @@ impl RenderState {
         &self.queue
     }
 
+    #[inline]
+    pub fn get_assets(&self) -> &AssetServer {
+        &self.assets
+    }
+
     #[inline]
     pub fn get_size(&self) -> winit::dpi::PhysicalSize<u32> {
         self.size
@@ -261,8 +253,14 @@ impl RenderState {
     }
 
     #[inline]
-    pub fn get_default_pipeline(&self) -> &wgpu::RenderPipeline {
-        &self.default_pipeline
+    pub fn get_default_pipeline(&self) -> Option<&wgpu::RenderPipeline> {
+        self.default_pipeline.as_ref()
+    }
+
+    /// Whether assets requested at startup are still loading in the background
+    #[inline]
+    pub fn is_loading(&self) -> bool {
+        self.loading
     }
 
     #[inline]
@@ -270,13 +268,115 @@ impl RenderState {
         &self.config
     }
 
+    /// Finishes any setup that was waiting on background loads, then picks up assets changed on disk.
+    /// Call once per frame before rendering.
+    pub fn update(&mut self) {
+        if self.loading {
+            self.finish_loading();
+        } else {
+            self.reload_changed_assets();
+        }
+    }
+
+    fn finish_loading(&mut self) {
+        let progress = self.assets.progress();
+        if !progress.is_done() {
+            return;
+        }
+
+        self.loading = false;
+        for error in [self.default_shader.error(), self.test_model.error()].into_iter().flatten() {
+            error!("{:#}", error);
+        }
+        if let Some(shader_text) = self.default_shader.get() {
+            self.default_pipeline = self.build_default_pipeline(shader_text);
+        }
+        info!("Asset loading finished: {} loaded, {} failed", progress.loaded, progress.failed);
+    }
+
+    /// Builds the default pipeline, logging the shader compiler's error and returning `None` if the shader is invalid
+    fn build_default_pipeline(&self, shader_text: &str) -> Option<wgpu::RenderPipeline> {
+        // Captures validation errors that would otherwise go to the device's uncaptured error handler and panic
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let pipeline = pipeline::create_instanced_pipeline(&self.device, &self.default_pipeline_layout, shader_text, self.config.format);
+        match pollster::block_on(self.device.pop_error_scope()) {
+            Some(e) => {
+                error!("Shader `{}` failed to compile:\n{}", self.default_shader.path(), e);
+                None
+            },
+            None => Some(pipeline),
+        }
+    }
+
+    /// Reloads assets whose files changed and rebuilds what was made from them. Anything that fails to reload
+    /// is logged and the last good version stays in use.
+    fn reload_changed_assets(&mut self) {
+        let changed = self.assets.take_changes();
+        let mut rebuild_model = false;
+        // Held until the model is rebuilt, so it picks up the reloaded versions instead of loading them again
+        let mut reloaded_textures = Vec::new();
+        let mut reloaded_strings = Vec::new();
+
+        for path in &changed {
+            info!("Asset changed: {}", path);
+            let model_depends = self.test_model.path() == path || self.test_model.get().is_some_and(|m| m.depends_on(path));
+
+            if path == self.default_shader.path() {
+                self.reload_default_shader();
+            } else if self.assets.get::<texture::Texture>(path).is_some() {
+                if let Some(texture) = self.reload::<texture::Texture>(path, &self.model_context.gpu) {
+                    reloaded_textures.push(texture);
+                    rebuild_model |= model_depends;
+                }
+            } else if self.assets.get::<String>(path).is_some() {
+                if let Some(text) = self.reload::<String>(path, &()) {
+                    reloaded_strings.push(text);
+                    rebuild_model |= model_depends;
+                }
+            } else {
+                rebuild_model |= model_depends;
+            }
+        }
+
+        if rebuild_model {
+            if let Some(test_model) = self.reload::<model::Model>(self.test_model.path(), &self.model_context) {
+                self.test_model = test_model;
+            }
+        }
+    }
+
+    fn reload_default_shader(&mut self) {
+        let Some(shader) = self.reload::<String>(self.default_shader.path(), &()) else {
+            return;
+        };
+
+        match self.build_default_pipeline(&shader) {
+            Some(pipeline) => {
+                self.default_pipeline = Some(pipeline);
+                self.default_shader = shader;
+                info!("Rebuilt the default pipeline");
+            },
+            None => warn!("Keeping the last working version of `{}`", self.default_shader.path()),
+        }
+    }
+
+    fn reload<T: Resource>(&self, path: &str, context: &T::Context) -> Option<Handle<T>> {
+        match self.assets.reload::<T>(path, context) {
+            Ok(handle) => Some(handle),
+            Err(e) => {
+                error!("Failed to reload `{}`, keeping the last good version: {:#}", path, e);
+                None
+            },
+        }
+    }
+
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
             self.size = new_size;
             self.config.width = new_size.width;
             self.config.height = new_size.height;
             self.surface.configure(&self.device, &self.config);
-            self.render_camera.resize(&self.device, &self.config);
+            self.render_camera.resize(&self.device, &self.queue, &self.config);
         }
     }
 }
@@ -1,5 +1,3 @@
-use wgpu::Color;
-
 use crate::{render_state::RenderState, model};
 
 
@@ -11,8 +9,8 @@ impl Renderer {
     pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, config: &wgpu::SurfaceConfiguration) -> Self {
 
         let smaa_target = smaa::SmaaTarget::new(
-            &device,
-            &queue,
+            device,
+            queue,
             config.width,
             config.height,
             config.format,
@@ -42,7 +40,7 @@ impl Renderer {
                     view: &smaa_frame,
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(Color { r: 0.4, g: 0.4, b: 0.4, a: 1.0 } ),
+                        load: wgpu::LoadOp::Clear(render_camera.get_clear_color()),
                         store: wgpu::StoreOp::Store,
                     }
                 })],
@@ -55,13 +53,16 @@ impl Renderer {
                 occlusion_query_set: None,
             });
 
-            render_pass.set_vertex_buffer(1, state.instance_buffer.slice(..));
+            // Until the pipeline and model have loaded the frame is only cleared, which doubles as the loading screen
+            if let (Some(pipeline), Some(test_model)) = (state.get_default_pipeline(), state.test_model.get()) {
+                render_pass.set_vertex_buffer(1, state.instance_buffer.slice(..));
 
-            render_camera.setup_bindings(&mut render_pass);
+                render_camera.setup_bindings(&mut render_pass);
 
-            render_pass.set_pipeline(state.get_default_pipeline());
+                render_pass.set_pipeline(pipeline);
 
-            model::draw_model_instanced(&mut render_pass, &state.test_model, 0..state.instances.len() as u32);
+                model::draw_model_instanced(&mut render_pass, test_model, 0..state.instances.len() as u32);
+            }
         }
 
         state.get_queue().submit(std::iter::once(encoder.finish()));
@@ -1,5 +1,14 @@
+use std::sync::Arc;
+
 use image::GenericImageView;
 
+/// The device and queue GPU resources are created with, shared so assets can be loaded away from `RenderState`
+#[derive(Clone)]
+pub struct GpuContext {
+    pub device: Arc<wgpu::Device>,
+    pub queue: Arc<wgpu::Queue>,
+}
+
 pub struct Texture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
@@ -11,7 +20,7 @@ impl Texture {
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
     pub fn from_bytes(device: &wgpu::Device, queue: &wgpu::Queue, bytes: &[u8], label: &str) -> anyhow::Result<Self> {
-        let img = image::load_from_memory(&bytes)?;
+        let img = image::load_from_memory(bytes)?;
         Self::from_image(device, queue, &img, Some(label))
     }
 
@@ -109,3 +118,11 @@ impl Texture {
     }
 
 }
+
+impl basalt_resource::Resource for Texture {
+    type Context = GpuContext;
+
+    fn load(file_name: &str, context: &GpuContext) -> anyhow::Result<Self> {
+        Self::from_bytes(&context.device, &context.queue, &basalt_resource::load_binary(file_name)?, file_name)
+    }
+}
@@ -1,31 +1,50 @@
 
-pub fn load_string(file_name: &str) -> anyhow::Result<String> {
+pub mod pack;
+mod pool;
+pub mod roots;
+pub mod server;
+
+use anyhow::Context;
 
-    // @todo: Replace this when we get a proper build system
-    let path = std::path::Path::new("")
-    // let path = std::path::Path::new(&std::env::var("OUT_DIR").unwrap())
-        .join("assets")
-        .join(file_name);
+pub use pack::{Compression, Pack, PackBuilder};
+pub use roots::{AssetRoots, read, resolve};
+pub use server::{AssetServer, Handle, LoadProgress, LoadState};
 
-    let text = std::fs::read_to_string(path)?;
+pub fn load_string(file_name: &str) -> anyhow::Result<String> {
+
+    let data = read(file_name)?;
+    let text = String::from_utf8(data)
+        .with_context(|| format!("`{}` is not valid UTF-8", file_name))?;
 
     Ok(text)
 }
 
 pub fn load_binary(file_name: &str) -> anyhow::Result<Vec<u8>> {
 
-    // @todo: Replace this when we get a proper build system
-    let path = std::path::Path::new("")
-    // let path = std::path::Path::new(&std::env::var("OUT_DIR").unwrap())
-        .join("assets")
-        .join(file_name);
+    read(file_name)
+}
+
 
-    let data = std::fs::read(path)?;
+/// Something that can be loaded from the assets folder, usually through an `AssetServer`
+pub trait Resource: Sized + Send + Sync + 'static {
+    /// Whatever else loading needs, such as the GPU device for textures. `()` for plain data.
+    type Context;
 
-    Ok(data)
+    fn load(file_name: &str, context: &Self::Context) -> anyhow::Result<Self>;
 }
 
+impl Resource for String {
+    type Context = ();
+
+    fn load(file_name: &str, _: &()) -> anyhow::Result<Self> {
+        load_string(file_name)
+    }
+}
 
-pub trait Resource: Sized {
-    fn load(file_name: &str) -> anyhow::Result<Self>;
-}
\ No newline at end of file
+impl Resource for Vec<u8> {
+    type Context = ();
+
+    fn load(file_name: &str, _: &()) -> anyhow::Result<Self> {
+        load_binary(file_name)
+    }
+}
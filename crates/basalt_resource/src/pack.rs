@@ -0,0 +1,492 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{bail, Context};
+
+use crate::server::normalize_path;
+
+// Layout, all integers little endian:
+//   magic "BPAK", u8 version, u32 entry count
+//   per entry: u16 path length, UTF-8 path, u8 compression, u64 offset, u64 stored size, u64 size, u64 hash
+//   entry data at the absolute offsets given in the table of contents
+
+const MAGIC: &[u8; 4] = b"BPAK";
+const VERSION: u8 = 1;
+
+/// Archive name looked for next to the executable and in the working directory
+pub const PACK_FILE: &str = "assets.bpak";
+
+/// Embeds a pack built by `basalt-pack` into the binary, for single file builds
+#[macro_export]
+macro_rules! include_pack {
+    ($path:expr) => {
+        $crate::pack::Pack::from_static($path, include_bytes!($path))
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Compression {
+    None,
+    Lz4,
+    Zstd,
+}
+
+impl Compression {
+    fn tag(self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Lz4 => 1,
+            Compression::Zstd => 2,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(Compression::None),
+            1 => Some(Compression::Lz4),
+            2 => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+
+    fn compress(self, data: &[u8], zstd_level: i32) -> std::io::Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(data.to_vec()),
+            Compression::Lz4 => Ok(lz4_flex::compress(data)),
+            Compression::Zstd => zstd::bulk::compress(data, zstd_level),
+        }
+    }
+
+    /// Whether `stored_size` bytes can decompress to `size` bytes, so a corrupt size is caught before it is allocated
+    fn can_hold(self, stored_size: u64, size: u64) -> bool {
+        match self {
+            Compression::None => size == stored_size,
+            // Every byte of an LZ4 block adds at most 255 bytes of output
+            Compression::Lz4 => size <= stored_size.saturating_mul(255),
+            // Zstd has no useful bound, `decompress` streams it instead of allocating `size` up front
+            Compression::Zstd => true,
+        }
+    }
+
+    fn decompress(self, data: &[u8], size: u64) -> anyhow::Result<Vec<u8>> {
+        Ok(match self {
+            Compression::None => data.to_vec(),
+            Compression::Lz4 => lz4_flex::decompress(data, size.try_into()?)?,
+            Compression::Zstd => {
+                let mut out = Vec::new();
+                zstd::stream::read::Decoder::with_buffer(data)?.take(size).read_to_end(&mut out)?;
+                out
+            },
+        })
+    }
+}
+
+impl std::str::FromStr for Compression {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "none" => Ok(Compression::None),
+            "lz4" => Ok(Compression::Lz4),
+            "zstd" => Ok(Compression::Zstd),
+            _ => bail!("unknown compression `{s}`, expected `none`, `lz4` or `zstd`"),
+        }
+    }
+}
+
+/// Content hash stored for every entry and checked when it is read back
+pub fn hash(data: &[u8]) -> u64 {
+    xxhash_rust::xxh3::xxh3_64(data)
+}
+
+/// One file in a pack's table of contents
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackEntry {
+    /// Normalized path relative to the asset root
+    pub path: String,
+    pub compression: Compression,
+    pub offset: u64,
+    /// Size of the entry as stored in the pack
+    pub stored_size: u64,
+    /// Size of the entry once decompressed
+    pub size: u64,
+    pub hash: u64,
+}
+
+enum PackData {
+    Static(&'static [u8]),
+    Owned(Vec<u8>),
+    File(Mutex<File>),
+}
+
+/// A read only archive of assets, opened from disk or embedded in the binary
+pub struct Pack {
+    name: PathBuf,
+    entries: HashMap<String, PackEntry>,
+    data: PackData,
+}
+
+impl std::fmt::Debug for Pack {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Pack").field("name", &self.name).field("entries", &self.entries.len()).finish()
+    }
+}
+
+fn read_u8(reader: &mut impl Read) -> std::io::Result<u8> {
+    let mut bytes = [0; 1];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+fn read_u16(reader: &mut impl Read) -> std::io::Result<u16> {
+    let mut bytes = [0; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+fn read_u32(reader: &mut impl Read) -> std::io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> std::io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Reads the table of contents, checking that every entry lies within the `len` bytes of the pack
+fn read_toc(reader: &mut impl Read, len: u64) -> anyhow::Result<HashMap<String, PackEntry>> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic).context("truncated header")?;
+    if &magic != MAGIC {
+        bail!("not an asset pack");
+    }
+    let version = read_u8(reader)?;
+    if version != VERSION {
+        bail!("unsupported pack version {version}, expected {VERSION}");
+    }
+
+    let count = read_u32(reader)?;
+    let mut entries = HashMap::new();
+    for _ in 0..count {
+        let path_len = read_u16(reader)? as usize;
+        let mut path = vec![0; path_len];
+        reader.read_exact(&mut path)?;
+        let path = String::from_utf8(path).context("entry path is not UTF-8")?;
+
+        let tag = read_u8(reader)?;
+        let compression = Compression::from_tag(tag).with_context(|| format!("`{path}` has unknown compression {tag}"))?;
+        let entry = PackEntry {
+            compression,
+            offset: read_u64(reader)?,
+            stored_size: read_u64(reader)?,
+            size: read_u64(reader)?,
+            hash: read_u64(reader)?,
+            path,
+        };
+        if entry.offset.checked_add(entry.stored_size).is_none_or(|end| end > len) {
+            bail!("`{}` lies outside the pack", entry.path);
+        }
+        if !entry.compression.can_hold(entry.stored_size, entry.size) {
+            bail!("`{}` claims a size of {} bytes that its {} stored bytes cannot hold", entry.path, entry.size, entry.stored_size);
+        }
+        entries.insert(entry.path.clone(), entry);
+    }
+
+    Ok(entries)
+}
+
+impl Pack {
+    /// Opens a pack on disk. Only the table of contents is read up front, entries are read as they are requested.
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let open = || -> anyhow::Result<Self> {
+            let mut file = File::open(path)?;
+            let len = file.metadata()?.len();
+            let entries = read_toc(&mut std::io::BufReader::new(&mut file), len)?;
+            Ok(Pack { name: path.to_path_buf(), entries, data: PackData::File(Mutex::new(file)) })
+        };
+        open().with_context(|| format!("failed to open pack `{}`", path.display()))
+    }
+
+    /// A pack held in memory. `name` is only used in error messages.
+    pub fn from_bytes(name: impl Into<PathBuf>, bytes: Vec<u8>) -> anyhow::Result<Self> {
+        let name = name.into();
+        let entries = read_toc(&mut bytes.as_slice(), bytes.len() as u64)
+            .with_context(|| format!("failed to read pack `{}`", name.display()))?;
+        Ok(Pack { name, entries, data: PackData::Owned(bytes) })
+    }
+
+    /// A pack embedded in the binary, usually through `include_pack!`
+    pub fn from_static(name: impl Into<PathBuf>, bytes: &'static [u8]) -> anyhow::Result<Self> {
+        let name = name.into();
+        let entries = read_toc(&mut &bytes[..], bytes.len() as u64)
+            .with_context(|| format!("failed to read pack `{}`", name.display()))?;
+        Ok(Pack { name, entries, data: PackData::Static(bytes) })
+    }
+
+    pub fn name(&self) -> &Path {
+        &self.name
+    }
+
+    pub fn entry(&self, file_name: &str) -> Option<&PackEntry> {
+        self.entries.get(&normalize_path(file_name))
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &PackEntry> {
+        self.entries.values()
+    }
+
+    pub fn contains(&self, file_name: &str) -> bool {
+        self.entry(file_name).is_some()
+    }
+
+    /// Reads and decompresses an entry, returning `None` if the pack does not contain it
+    pub fn read(&self, file_name: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let Some(entry) = self.entry(file_name) else {
+            return Ok(None);
+        };
+
+        let range = entry.offset as usize..(entry.offset + entry.stored_size) as usize;
+        let stored = match &self.data {
+            PackData::Static(bytes) => bytes[range].to_vec(),
+            PackData::Owned(bytes) => bytes[range].to_vec(),
+            PackData::File(file) => {
+                let mut file = file.lock().unwrap();
+                let mut stored = vec![0; entry.stored_size as usize];
+                file.seek(SeekFrom::Start(entry.offset))?;
+                file.read_exact(&mut stored)?;
+                stored
+            },
+        };
+
+        let data = entry.compression.decompress(&stored, entry.size)
+            .with_context(|| format!("`{}` in pack `{}` failed to decompress", entry.path, self.name.display()))?;
+        if data.len() as u64 != entry.size || hash(&data) != entry.hash {
+            bail!("`{}` in pack `{}` is corrupt, its content does not match the stored hash", entry.path, self.name.display());
+        }
+        Ok(Some(data))
+    }
+}
+
+/// Collects files and writes them out as a pack
+pub struct PackBuilder {
+    entries: Vec<(PackEntry, Vec<u8>)>,
+    zstd_level: i32,
+}
+
+impl Default for PackBuilder {
+    fn default() -> Self {
+        PackBuilder { entries: Vec::new(), zstd_level: zstd::DEFAULT_COMPRESSION_LEVEL }
+    }
+}
+
+impl PackBuilder {
+    pub fn new() -> Self {
+        PackBuilder::default()
+    }
+
+    pub fn with_zstd_level(mut self, level: i32) -> Self {
+        self.zstd_level = level;
+        self
+    }
+
+    /// Adds or replaces an entry. Entries that do not shrink under `compression` are stored uncompressed.
+    pub fn add(&mut self, file_name: &str, data: &[u8], compression: Compression) -> anyhow::Result<()> {
+        let path = normalize_path(file_name);
+        if path.len() > u16::MAX as usize {
+            bail!("path `{path}` is too long for a pack");
+        }
+
+        let compressed = compression.compress(data, self.zstd_level)?;
+        let (compression, stored) = if compressed.len() < data.len() {
+            (compression, compressed)
+        } else {
+            (Compression::None, data.to_vec())
+        };
+
+        let entry = PackEntry {
+            path,
+            compression,
+            offset: 0,
+            stored_size: stored.len() as u64,
+            size: data.len() as u64,
+            hash: hash(data),
+        };
+        self.entries.retain(|(e, _)| e.path != entry.path);
+        self.entries.push((entry, stored));
+        Ok(())
+    }
+
+    /// Adds every file under `dir`, named by their path relative to it. Returns the number of files added.
+    pub fn add_dir(&mut self, dir: impl AsRef<Path>, compression: Compression) -> anyhow::Result<usize> {
+        let dir = dir.as_ref();
+        let mut files = Vec::new();
+        let mut open = vec![dir.to_path_buf()];
+        while let Some(current) = open.pop() {
+            let read = std::fs::read_dir(&current).with_context(|| format!("failed to read `{}`", current.display()))?;
+            for item in read {
+                let path = item?.path();
+                if path.is_dir() {
+                    open.push(path);
+                } else {
+                    files.push(path);
+                }
+            }
+        }
+
+        // Sorted so the same folder always produces the same pack
+        files.sort();
+        for file in &files {
+            let name = file.strip_prefix(dir)?.to_str()
+                .with_context(|| format!("`{}` is not a UTF-8 path", file.display()))?;
+            let data = std::fs::read(file).with_context(|| format!("failed to read `{}`", file.display()))?;
+            self.add(name, &data, compression)?;
+        }
+        Ok(files.len())
+    }
+
+    /// Entries added so far. Their offsets are only assigned when the pack is written.
+    pub fn entries(&self) -> impl Iterator<Item = &PackEntry> {
+        self.entries.iter().map(|(entry, _)| entry)
+    }
+
+    pub fn write(&self, out: &mut impl Write) -> std::io::Result<()> {
+        let mut entries = self.entries.iter().collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.0.path.cmp(&b.0.path));
+
+        let toc_len = MAGIC.len() + 1 + 4 + entries.iter().map(|(e, _)| 2 + e.path.len() + 1 + 8 * 4).sum::<usize>();
+
+        out.write_all(MAGIC)?;
+        out.write_all(&[VERSION])?;
+        out.write_all(&(entries.len() as u32).to_le_bytes())?;
+
+        let mut offset = toc_len as u64;
+        for (entry, _) in &entries {
+            out.write_all(&(entry.path.len() as u16).to_le_bytes())?;
+            out.write_all(entry.path.as_bytes())?;
+            out.write_all(&[entry.compression.tag()])?;
+            out.write_all(&offset.to_le_bytes())?;
+            out.write_all(&entry.stored_size.to_le_bytes())?;
+            out.write_all(&entry.size.to_le_bytes())?;
+            out.write_all(&entry.hash.to_le_bytes())?;
+            offset += entry.stored_size;
+        }
+
+        for (_, stored) in &entries {
+            out.write_all(stored)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_file(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let mut out = std::io::BufWriter::new(File::create(path).with_context(|| format!("failed to create `{}`", path.display()))?);
+        self.write(&mut out)?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> PackBuilder {
+        let mut builder = PackBuilder::new();
+        let text = "hex ".repeat(256);
+        builder.add("plain.txt", b"short", Compression::None).unwrap();
+        builder.add("lz4/text.txt", text.as_bytes(), Compression::Lz4).unwrap();
+        builder.add("zstd\\text.txt", text.as_bytes(), Compression::Zstd).unwrap();
+        builder
+    }
+
+    fn bytes(builder: &PackBuilder) -> Vec<u8> {
+        let mut out = Vec::new();
+        builder.write(&mut out).unwrap();
+        out
+    }
+
+    /// Offset of the `size` field of the first entry, `lz4/text.txt` since entries are written sorted by path
+    fn first_size_offset() -> usize {
+        MAGIC.len() + 1 + 4 + 2 + "lz4/text.txt".len() + 1 + 8 + 8
+    }
+
+    #[test]
+    fn round_trips_every_compression() {
+        let builder = sample();
+        let pack = Pack::from_bytes("sample.bpak", bytes(&builder)).unwrap();
+        let text = "hex ".repeat(256);
+
+        assert_eq!(pack.entry("lz4/text.txt").unwrap().compression, Compression::Lz4);
+        assert_eq!(pack.entry("zstd/text.txt").unwrap().compression, Compression::Zstd);
+        assert_eq!(pack.read("plain.txt").unwrap().unwrap(), b"short");
+        assert_eq!(pack.read("./lz4//text.txt").unwrap().unwrap(), text.as_bytes());
+        assert_eq!(pack.read("zstd/text.txt").unwrap().unwrap(), text.as_bytes());
+        assert_eq!(pack.read("missing.txt").unwrap(), None);
+
+        let file = std::env::temp_dir().join(format!("basalt-pack-{}.bpak", std::process::id()));
+        builder.write_file(&file).unwrap();
+        let opened = Pack::open(&file).unwrap();
+        for entry in builder.entries() {
+            assert_eq!(opened.entry(&entry.path).unwrap().hash, entry.hash);
+            assert_eq!(opened.read(&entry.path).unwrap(), pack.read(&entry.path).unwrap());
+        }
+        drop(opened);
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn incompressible_entries_are_stored_as_is() {
+        let mut builder = PackBuilder::new();
+        builder.add("tiny.txt", b"ab", Compression::Zstd).unwrap();
+        assert_eq!(builder.entries().next().unwrap().compression, Compression::None);
+    }
+
+    #[test]
+    fn rejects_content_that_does_not_match_its_hash() {
+        let mut builder = PackBuilder::new();
+        builder.add("plain.txt", b"short", Compression::None).unwrap();
+        let mut data = bytes(&builder);
+        *data.last_mut().unwrap() ^= 1;
+
+        let pack = Pack::from_bytes("flipped.bpak", data).unwrap();
+        let error = pack.read("plain.txt").unwrap_err();
+        assert!(error.to_string().contains("does not match the stored hash"), "{error:#}");
+    }
+
+    #[test]
+    fn rejects_a_truncated_table_of_contents() {
+        let data = bytes(&sample());
+        for len in 0..first_size_offset() + 16 {
+            assert!(Pack::from_bytes("truncated.bpak", data[..len].to_vec()).is_err(), "accepted {len} bytes");
+        }
+        assert!(Pack::from_bytes("bad.bpak", b"PKZIP".to_vec()).is_err());
+    }
+
+    #[test]
+    fn rejects_sizes_the_stored_bytes_cannot_hold() {
+        let mut data = bytes(&sample());
+        let at = first_size_offset();
+        data[at..at + 8].copy_from_slice(&(1u64 << 62).to_le_bytes());
+        let error = Pack::from_bytes("huge.bpak", data).unwrap_err();
+        assert!(format!("{error:#}").contains("cannot hold"), "{error:#}");
+    }
+
+    #[test]
+    fn zstd_sizes_are_capped_while_decompressing() {
+        let mut builder = PackBuilder::new();
+        builder.add("zstd.txt", "hex ".repeat(256).as_bytes(), Compression::Zstd).unwrap();
+        let mut data = bytes(&builder);
+        let at = MAGIC.len() + 1 + 4 + 2 + "zstd.txt".len() + 1 + 8 + 8;
+        data[at..at + 8].copy_from_slice(&(1u64 << 62).to_le_bytes());
+
+        let pack = Pack::from_bytes("huge.bpak", data).unwrap();
+        assert!(pack.read("zstd.txt").unwrap_err().to_string().contains("corrupt"));
+    }
+}
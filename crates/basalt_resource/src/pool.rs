@@ -0,0 +1,37 @@
+use std::sync::{mpsc, Arc, Mutex};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Fixed set of threads running queued jobs in order. The threads exit once the pool is dropped and the queue drains.
+pub(crate) struct WorkerPool {
+    sender: mpsc::Sender<Job>,
+}
+
+impl WorkerPool {
+    pub(crate) fn new(threads: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        for index in 0..threads.max(1) {
+            let receiver = receiver.clone();
+            std::thread::Builder::new()
+                .name(format!("basalt-asset-{index}"))
+                .spawn(move || loop {
+                    // The lock is released before running the job so other workers can pick up the next one
+                    let job = receiver.lock().unwrap().recv();
+                    match job {
+                        Ok(job) => job(),
+                        Err(_) => break,
+                    }
+                })
+                .expect("failed to spawn asset worker thread");
+        }
+
+        WorkerPool { sender }
+    }
+
+    pub(crate) fn spawn(&self, job: impl FnOnce() + Send + 'static) {
+        // Workers only stop once the sender is dropped, so sending cannot fail while the pool is alive
+        let _ = self.sender.send(Box::new(job));
+    }
+}
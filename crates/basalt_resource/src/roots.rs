@@ -0,0 +1,233 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use anyhow::Context;
+
+use crate::pack::{Pack, PACK_FILE};
+
+/// Environment variable listing asset directories and packs, separated like `PATH`. The first entry has the highest priority.
+pub const ASSET_PATH_VAR: &str = "BASALT_ASSET_PATH";
+
+/// Name of the assets folder looked for next to the executable and in the working directory
+pub const ASSETS_DIR: &str = "assets";
+
+static ROOTS: RwLock<Option<AssetRoots>> = RwLock::new(None);
+
+/// Ordered list of directories assets are searched in. Earlier directories overlay later ones, so a mod or
+/// patch directory placed in front of the base assets replaces any file the two have in common.
+/// Packs are searched after every directory, so loose files patch over packed ones.
+#[derive(Debug, Clone, Default)]
+pub struct AssetRoots {
+    dirs: Vec<PathBuf>,
+    packs: Vec<Arc<Pack>>,
+}
+
+fn open_pack(path: &Path) -> Option<Pack> {
+    match Pack::open(path) {
+        Ok(pack) => Some(pack),
+        Err(e) => {
+            log::warn!("Skipping asset pack: {:#}", e);
+            None
+        },
+    }
+}
+
+impl AssetRoots {
+    pub fn new() -> Self {
+        AssetRoots::default()
+    }
+
+    /// The default search order: every entry of `BASALT_ASSET_PATH`, then `assets` next to the executable,
+    /// then `assets` in the working directory. Entries of `BASALT_ASSET_PATH` that are files are opened as packs,
+    /// as is any `assets.bpak` next to the executable or in the working directory.
+    pub fn discover() -> Self {
+        let mut roots = AssetRoots::new();
+        let exe_dir = std::env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf));
+
+        if let Some(paths) = std::env::var_os(ASSET_PATH_VAR) {
+            for path in std::env::split_paths(&paths).filter(|p| !p.as_os_str().is_empty()) {
+                if path.is_file() {
+                    roots.packs.extend(open_pack(&path).map(Arc::new));
+                } else {
+                    roots.push_base(path);
+                }
+            }
+        }
+        if let Some(exe_dir) = &exe_dir {
+            roots.push_base(exe_dir.join(ASSETS_DIR));
+        }
+        roots.push_base(PathBuf::from(ASSETS_DIR));
+
+        let pack_files = exe_dir.iter().map(|dir| dir.join(PACK_FILE)).chain([PathBuf::from(PACK_FILE)]);
+        for path in pack_files {
+            if path.is_file() && !roots.packs.iter().any(|p| same_file(p.name(), &path)) {
+                roots.packs.extend(open_pack(&path).map(Arc::new));
+            }
+        }
+
+        roots
+    }
+
+    /// Adds a directory that takes priority over every directory already added
+    pub fn push_overlay(&mut self, dir: impl Into<PathBuf>) {
+        let dir = dir.into();
+        self.dirs.retain(|d| *d != dir);
+        self.dirs.insert(0, dir);
+    }
+
+    /// Adds a directory that is only searched when no directory already added has the file
+    pub fn push_base(&mut self, dir: impl Into<PathBuf>) {
+        let dir = dir.into();
+        if !self.dirs.contains(&dir) {
+            self.dirs.push(dir);
+        }
+    }
+
+    pub fn with_overlay(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.push_overlay(dir);
+        self
+    }
+
+    /// Adds a pack that is only searched when no directory or pack already added has the file
+    pub fn push_pack(&mut self, pack: impl Into<Arc<Pack>>) {
+        self.packs.push(pack.into());
+    }
+
+    pub fn with_pack(mut self, pack: impl Into<Arc<Pack>>) -> Self {
+        self.push_pack(pack);
+        self
+    }
+
+    /// Directories in search order, highest priority first
+    pub fn dirs(&self) -> &[PathBuf] {
+        &self.dirs
+    }
+
+    /// Packs in search order, highest priority first
+    pub fn packs(&self) -> &[Arc<Pack>] {
+        &self.packs
+    }
+
+    /// Finds the highest priority loose file named `file_name`. Files only found in packs have no path, use `read` for those.
+    pub fn resolve(&self, file_name: &str) -> Result<PathBuf, ResolveError> {
+        let tried = self.dirs.iter().map(|dir| dir.join(file_name)).collect::<Vec<_>>();
+        match tried.iter().find(|path| path.is_file()) {
+            Some(path) => Ok(path.clone()),
+            None => Err(ResolveError { file_name: file_name.to_string(), tried }),
+        }
+    }
+
+    /// Reads the highest priority file named `file_name`, from a directory or else from a pack
+    pub fn read(&self, file_name: &str) -> anyhow::Result<Vec<u8>> {
+        let mut error = match self.resolve(file_name) {
+            Ok(path) => return std::fs::read(&path).with_context(|| format!("failed to read `{}`", path.display())),
+            Err(e) => e,
+        };
+
+        for pack in &self.packs {
+            if let Some(data) = pack.read(file_name)? {
+                return Ok(data);
+            }
+            error.tried.push(pack.name().join(file_name));
+        }
+        Err(error.into())
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    a == b || a.canonicalize().ok().is_some_and(|a| b.canonicalize().ok() == Some(a))
+}
+
+/// Returned when no asset root contains a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolveError {
+    pub file_name: String,
+    /// Every candidate path, in the order they were checked
+    pub tried: Vec<PathBuf>,
+}
+
+impl std::fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.tried.is_empty() {
+            return write!(f, "asset `{}` not found, no asset roots are configured", self.file_name);
+        }
+        write!(f, "asset `{}` not found, tried:", self.file_name)?;
+        for path in &self.tried {
+            write!(f, "\n    {}", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ResolveError {}
+
+/// Replaces the roots used by `load_string`, `load_binary`, `read` and `resolve`
+pub fn set_roots(roots: AssetRoots) {
+    *ROOTS.write().unwrap() = Some(roots);
+}
+
+/// The roots currently in use, discovered on first use unless `set_roots` was called before
+pub fn roots() -> AssetRoots {
+    if let Some(roots) = ROOTS.read().unwrap().as_ref() {
+        return roots.clone();
+    }
+    ROOTS.write().unwrap().get_or_insert_with(AssetRoots::discover).clone()
+}
+
+/// Resolves `file_name` against the current roots
+pub fn resolve(file_name: &str) -> Result<PathBuf, ResolveError> {
+    roots().resolve(file_name)
+}
+
+/// Reads `file_name` through the current roots
+pub fn read(file_name: &str) -> anyhow::Result<Vec<u8>> {
+    roots().read(file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pack::{Compression, PackBuilder};
+
+    fn pack(name: &str, files: &[(&str, &str)]) -> Pack {
+        let mut builder = PackBuilder::new();
+        for (file_name, text) in files {
+            builder.add(file_name, text.as_bytes(), Compression::None).unwrap();
+        }
+        let mut bytes = Vec::new();
+        builder.write(&mut bytes).unwrap();
+        Pack::from_bytes(name, bytes).unwrap()
+    }
+
+    #[test]
+    fn loose_files_overlay_packs_and_earlier_packs_win() {
+        let dir = std::env::temp_dir().join(format!("basalt-roots-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("patched.txt"), "loose").unwrap();
+
+        let roots = AssetRoots::new()
+            .with_overlay(&dir)
+            .with_pack(pack("first.bpak", &[("patched.txt", "first"), ("shared.txt", "first")]))
+            .with_pack(pack("second.bpak", &[("shared.txt", "second"), ("last.txt", "second")]));
+
+        assert_eq!(roots.read("patched.txt").unwrap(), b"loose");
+        assert_eq!(roots.read("shared.txt").unwrap(), b"first");
+        assert_eq!(roots.read("last.txt").unwrap(), b"second");
+        assert!(roots.resolve("shared.txt").is_err());
+
+        let error = roots.read("missing.txt").unwrap_err().downcast::<ResolveError>().unwrap();
+        assert_eq!(error.tried, [dir.join("missing.txt"), PathBuf::from("first.bpak/missing.txt"), PathBuf::from("second.bpak/missing.txt")]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn overlays_take_priority_over_bases() {
+        let mut roots = AssetRoots::new();
+        roots.push_base("base");
+        roots.push_overlay("mod");
+        roots.push_base("fallback");
+        roots.push_overlay("base");
+        assert_eq!(roots.dirs(), [PathBuf::from("base"), PathBuf::from("mod"), PathBuf::from("fallback")]);
+    }
+}
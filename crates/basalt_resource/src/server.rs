@@ -0,0 +1,539 @@
+use std::any::{Any, TypeId};
+use std::collections::{HashMap, HashSet};
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, OnceLock, Weak};
+use std::time::{Duration, SystemTime};
+
+use crate::Resource;
+use crate::pool::WorkerPool;
+
+/// Turns an asset name into the form used as its cache key, so `./models//hex.obj`, `models\hex.obj` and
+/// `textures/../models/hex.obj` all share one entry. Parent components that would leave the asset root are dropped.
+pub fn normalize_path(file_name: &str) -> String {
+    let mut parts = Vec::new();
+    for part in file_name.split(['/', '\\']) {
+        match part {
+            "" | "." => {},
+            ".." => {
+                parts.pop();
+            },
+            _ => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LoadState {
+    Loading,
+    Loaded,
+    Failed,
+}
+
+/// Counts of the loads started since the server was last idle, for driving a loading screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LoadProgress {
+    pub total: usize,
+    pub loaded: usize,
+    pub failed: usize,
+}
+
+impl LoadProgress {
+    pub fn finished(&self) -> usize {
+        self.loaded + self.failed
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.finished() >= self.total
+    }
+
+    /// Fraction of loads that have finished, `1.0` when nothing is loading
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            return 1.0;
+        }
+        self.finished() as f32 / self.total as f32
+    }
+}
+
+struct Asset<T> {
+    path: String,
+    /// Set by whichever thread starts loading, so a queued load and a blocking load of the same asset never both run
+    claimed: AtomicBool,
+    result: OnceLock<Result<T, Arc<anyhow::Error>>>,
+    finished: Mutex<()>,
+    signal: Condvar,
+}
+
+impl<T> Asset<T> {
+    fn new(path: String) -> Self {
+        Asset { path, claimed: AtomicBool::new(false), result: OnceLock::new(), finished: Mutex::new(()), signal: Condvar::new() }
+    }
+
+    fn claim(&self) -> bool {
+        !self.claimed.swap(true, Ordering::AcqRel)
+    }
+
+    fn finish(&self, result: Result<T, Arc<anyhow::Error>>) {
+        let _ = self.result.set(result);
+        // Taking the lock orders the notification after any waiter has checked the result and gone to sleep
+        let _guard = self.finished.lock().unwrap();
+        self.signal.notify_all();
+    }
+
+    fn wait(&self) -> &Result<T, Arc<anyhow::Error>> {
+        let mut guard = self.finished.lock().unwrap();
+        loop {
+            if let Some(result) = self.result.get() {
+                return result;
+            }
+            guard = self.signal.wait(guard).unwrap();
+        }
+    }
+}
+
+/// A load error shared between every handle to a failed asset
+#[derive(Debug)]
+struct SharedError(Arc<anyhow::Error>);
+
+impl std::fmt::Display for SharedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl std::error::Error for SharedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+/// A shared, reference counted asset that may still be loading. The asset is freed once every handle to it is dropped.
+pub struct Handle<T> {
+    asset: Arc<Asset<T>>,
+}
+
+impl<T> Handle<T> {
+    /// Normalized path the asset was loaded from
+    pub fn path(&self) -> &str {
+        &self.asset.path
+    }
+
+    /// Number of live handles to this asset
+    pub fn ref_count(&self) -> usize {
+        Arc::strong_count(&self.asset)
+    }
+
+    /// Whether both handles point at the same asset
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.asset, &other.asset)
+    }
+
+    pub fn state(&self) -> LoadState {
+        match self.asset.result.get() {
+            None => LoadState::Loading,
+            Some(Ok(_)) => LoadState::Loaded,
+            Some(Err(_)) => LoadState::Failed,
+        }
+    }
+
+    /// The asset, once it has loaded
+    pub fn get(&self) -> Option<&T> {
+        self.asset.result.get()?.as_ref().ok()
+    }
+
+    /// Why the asset failed to load, if it did
+    pub fn error(&self) -> Option<&anyhow::Error> {
+        self.asset.result.get()?.as_ref().err().map(|e| e.as_ref())
+    }
+
+    /// Blocks until the asset has finished loading
+    pub fn wait(&self) -> anyhow::Result<&T> {
+        self.asset.wait().as_ref().map_err(|e| SharedError(e.clone()).into())
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Handle { asset: self.asset.clone() }
+    }
+}
+
+/// Panics if the asset has not loaded. Handles returned by `AssetServer::load` always have.
+impl<T> std::ops::Deref for Handle<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.get().unwrap_or_else(|| panic!("asset `{}` is not loaded", self.asset.path))
+    }
+}
+
+impl<T> std::fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Handle")
+            .field("path", &self.asset.path)
+            .field("state", &self.state())
+            .field("ref_count", &self.ref_count())
+            .finish()
+    }
+}
+
+type AssetKey = (TypeId, String);
+
+/// Weak pointers to the versions of one asset, oldest first. A reload adds a version rather than replacing the
+/// last one, so a reloaded asset that gets dropped falls back to the version still in use.
+struct Versions<T>(Vec<Weak<Asset<T>>>);
+
+impl<T> Versions<T> {
+    fn latest(&self) -> Option<Arc<Asset<T>>> {
+        self.0.iter().rev().find_map(Weak::upgrade)
+    }
+}
+
+/// Type erased cache entry, the `Versions` of an `Asset<T>`
+trait Entry: Send + Sync {
+    fn is_alive(&self) -> bool;
+    /// Forgets versions without live handles
+    fn prune(&mut self);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Send + Sync + 'static> Entry for Versions<T> {
+    fn is_alive(&self) -> bool {
+        self.0.iter().any(|weak| weak.strong_count() > 0)
+    }
+
+    fn prune(&mut self) {
+        self.0.retain(|weak| weak.strong_count() > 0);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Where an asset's file resolved to and when it was last modified, `None` if it could not be found
+type Stamp = Option<(PathBuf, Option<SystemTime>)>;
+
+fn stamp(path: &str) -> Stamp {
+    let file = crate::resolve(path).ok()?;
+    let modified = std::fs::metadata(&file).and_then(|m| m.modified()).ok();
+    Some((file, modified))
+}
+
+struct Shared {
+    cache: Mutex<HashMap<AssetKey, Box<dyn Entry>>>,
+    progress: Mutex<LoadProgress>,
+    /// Started on the first background load
+    workers: OnceLock<WorkerPool>,
+    threads: usize,
+    /// File stamps of loaded assets by normalized path, compared against the disk while watching
+    stamps: Mutex<HashMap<String, Stamp>>,
+    changes: Mutex<Vec<String>>,
+    watching: AtomicBool,
+}
+
+impl Shared {
+    fn poll_changes(&self) {
+        let live = self.cache.lock().unwrap().iter()
+            .filter(|(_, entry)| entry.is_alive())
+            .map(|((_, path), _)| path.clone())
+            .collect::<HashSet<_>>();
+
+        let mut stamps = self.stamps.lock().unwrap();
+        stamps.retain(|path, _| live.contains(path));
+
+        let mut changed = Vec::new();
+        for (path, old) in stamps.iter_mut() {
+            let new = stamp(path);
+            if new != *old {
+                *old = new;
+                changed.push(path.clone());
+            }
+        }
+        drop(stamps);
+
+        let mut changes = self.changes.lock().unwrap();
+        for path in changed {
+            if !changes.contains(&path) {
+                changes.push(path);
+            }
+        }
+    }
+}
+
+/// Loads resources through their `Resource` impl and hands out shared handles. Loading a path that already has
+/// live handles returns another handle to the same asset instead of reading the file again, and a path that
+/// failed is tried again. Cloning the server is cheap and every clone shares one cache.
+#[derive(Clone)]
+pub struct AssetServer {
+    shared: Arc<Shared>,
+}
+
+impl Default for AssetServer {
+    fn default() -> Self {
+        let threads = std::thread::available_parallelism().map_or(2, |n| n.get()).min(4);
+        AssetServer::with_workers(threads)
+    }
+}
+
+impl AssetServer {
+    pub fn new() -> Self {
+        AssetServer::default()
+    }
+
+    /// A server whose background loads run on `threads` worker threads
+    pub fn with_workers(threads: usize) -> Self {
+        AssetServer {
+            shared: Arc::new(Shared {
+                cache: Mutex::new(HashMap::new()),
+                progress: Mutex::new(LoadProgress::default()),
+                workers: OnceLock::new(),
+                threads,
+                stamps: Mutex::new(HashMap::new()),
+                changes: Mutex::new(Vec::new()),
+                watching: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    /// Returns the cached asset at `file_name` in whatever state it is in, if anything holds a handle to it
+    pub fn get<T: Resource>(&self, file_name: &str) -> Option<Handle<T>> {
+        let key = (TypeId::of::<T>(), normalize_path(file_name));
+        Self::lookup(&self.shared.cache.lock().unwrap(), &key)
+    }
+
+    /// Loads the asset at `file_name` on the calling thread, or waits for it if a worker is already loading it
+    pub fn load<T: Resource>(&self, file_name: &str, context: &T::Context) -> anyhow::Result<Handle<T>> {
+        let (handle, _) = self.entry::<T>(file_name);
+
+        // A queued background load that has not started yet is taken over rather than waited on, so loading
+        // from inside a worker cannot stall behind jobs stuck in the queue
+        if handle.asset.claim() {
+            self.run(&handle.asset, context);
+        }
+        handle.wait()?;
+        Ok(handle)
+    }
+
+    /// Queues the asset at `file_name` on the worker threads and returns a handle that is `Loading` until it finishes
+    pub fn load_async<T: Resource>(&self, file_name: &str, context: &T::Context) -> Handle<T>
+    where
+        T::Context: Clone + Send + 'static,
+    {
+        let (handle, is_new) = self.entry::<T>(file_name);
+        if is_new {
+            let server = self.clone();
+            let asset = handle.asset.clone();
+            let context = context.clone();
+            self.workers().spawn(move || {
+                if asset.claim() {
+                    server.run(&asset, &context);
+                }
+            });
+        }
+        handle
+    }
+
+    /// Loads the asset at `file_name` again on the calling thread, ignoring the cache. On success the new asset
+    /// is returned by later loads for as long as it has live handles, while existing handles keep the old one until
+    /// they are swapped out. If every handle to the new asset is dropped, or the reload fails, later loads get the
+    /// last version still in use and its file stays watched.
+    pub fn reload<T: Resource>(&self, file_name: &str, context: &T::Context) -> anyhow::Result<Handle<T>> {
+        let path = normalize_path(file_name);
+        let handle = Handle::<T> { asset: Arc::new(Asset::new(path.clone())) };
+        handle.asset.claim();
+        self.begin_load();
+        self.run(&handle.asset, context);
+        handle.wait()?;
+
+        let mut cache = self.shared.cache.lock().unwrap();
+        Self::push_version(&mut cache, (TypeId::of::<T>(), path), &handle);
+        Ok(handle)
+    }
+
+    /// Starts a background thread that checks the files behind every live asset each `interval` and records
+    /// the ones that changed, for `take_changes` to pick up. A file counts as changed when it is modified, removed,
+    /// or shadowed by a new file in a higher priority asset root. Calling this again does nothing.
+    pub fn watch(&self, interval: Duration) {
+        if self.shared.watching.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        let shared = Arc::downgrade(&self.shared);
+        std::thread::Builder::new()
+            .name("basalt-asset-watcher".to_string())
+            .spawn(move || loop {
+                std::thread::sleep(interval);
+                match shared.upgrade() {
+                    Some(shared) => shared.poll_changes(),
+                    None => break,
+                }
+            })
+            .expect("failed to spawn asset watcher thread");
+    }
+
+    /// Normalized paths of the assets whose files changed since the last call. Always empty unless `watch` was called.
+    pub fn take_changes(&self) -> Vec<String> {
+        std::mem::take(&mut *self.shared.changes.lock().unwrap())
+    }
+
+    /// Progress of every load started since the server was last idle
+    pub fn progress(&self) -> LoadProgress {
+        *self.shared.progress.lock().unwrap()
+    }
+
+    /// Number of assets with live handles
+    pub fn len(&self) -> usize {
+        self.shared.cache.lock().unwrap().values().filter(|entry| entry.is_alive()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn workers(&self) -> &WorkerPool {
+        self.shared.workers.get_or_init(|| WorkerPool::new(self.shared.threads))
+    }
+
+    fn lookup<T: Resource>(cache: &HashMap<AssetKey, Box<dyn Entry>>, key: &AssetKey) -> Option<Handle<T>> {
+        let versions = cache.get(key)?.as_any().downcast_ref::<Versions<T>>()?;
+        versions.latest().map(|asset| Handle { asset })
+    }
+
+    fn push_version<T: Resource>(cache: &mut HashMap<AssetKey, Box<dyn Entry>>, key: AssetKey, handle: &Handle<T>) {
+        let entry = cache.entry(key).or_insert_with(|| Box::new(Versions::<T>(Vec::new())));
+        entry.prune();
+        if let Some(versions) = entry.as_any_mut().downcast_mut::<Versions<T>>() {
+            versions.0.push(Arc::downgrade(&handle.asset));
+        }
+    }
+
+    /// The live entry for `file_name`, or a new one if there is none or the last attempt failed.
+    /// Also returns whether the entry is new, in which case it still has to be loaded.
+    fn entry<T: Resource>(&self, file_name: &str) -> (Handle<T>, bool) {
+        let path = normalize_path(file_name);
+        let key = (TypeId::of::<T>(), path.clone());
+
+        let mut cache = self.shared.cache.lock().unwrap();
+        if let Some(handle) = Self::lookup::<T>(&cache, &key).filter(|h| h.state() != LoadState::Failed) {
+            return (handle, false);
+        }
+
+        let handle = Handle { asset: Arc::new(Asset::new(path)) };
+        cache.retain(|_, entry| entry.is_alive());
+        Self::push_version(&mut cache, key, &handle);
+        drop(cache);
+
+        self.begin_load();
+        (handle, true)
+    }
+
+    fn begin_load(&self) {
+        let mut progress = self.shared.progress.lock().unwrap();
+        if progress.is_done() {
+            *progress = LoadProgress::default();
+        }
+        progress.total += 1;
+    }
+
+    fn run<T: Resource>(&self, asset: &Asset<T>, context: &T::Context) {
+        // Stamped before loading, so an edit made while the file is being read is still picked up
+        let stamp = stamp(&asset.path);
+
+        // A panicking loader must still finish the asset, otherwise anything waiting on it would hang
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| T::load(&asset.path, context)))
+            .unwrap_or_else(|_| Err(anyhow::anyhow!("loading `{}` panicked", asset.path)));
+        let loaded = result.is_ok();
+        // Failed assets are stamped too, so fixing or adding the file shows up as a change
+        self.shared.stamps.lock().unwrap().insert(asset.path.clone(), stamp);
+        asset.finish(result.map_err(Arc::new));
+
+        let mut progress = self.shared.progress.lock().unwrap();
+        if loaded {
+            progress.loaded += 1;
+        } else {
+            progress.failed += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::*;
+    use crate::AssetRoots;
+
+    fn touch(path: &std::path::Path, text: &str) {
+        std::fs::write(path, text).unwrap();
+        // Pushed well into the future so the change is seen even on file systems with coarse timestamps
+        let file = std::fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60)).unwrap();
+    }
+
+    /// The asset roots are global, so every test shares one directory and sticks to its own files in it
+    fn asset_dir() -> &'static std::path::Path {
+        static DIR: OnceLock<std::path::PathBuf> = OnceLock::new();
+        DIR.get_or_init(|| {
+            let dir = std::env::temp_dir().join(format!("basalt-watch-{}", std::process::id()));
+            std::fs::create_dir_all(dir.join("models")).unwrap();
+            crate::roots::set_roots(AssetRoots::new().with_overlay(&dir));
+            dir
+        })
+    }
+
+    #[test]
+    fn only_assets_with_live_handles_are_watched() {
+        let dir = asset_dir();
+        std::fs::write(dir.join("models/kept.mtl"), "old").unwrap();
+        std::fs::write(dir.join("dropped.txt"), "old").unwrap();
+
+        let server = AssetServer::with_workers(1);
+        let kept = server.load::<String>("models\\kept.mtl", &()).unwrap();
+        drop(server.load::<String>("dropped.txt", &()).unwrap());
+
+        touch(&dir.join("models/kept.mtl"), "new");
+        touch(&dir.join("dropped.txt"), "new");
+        server.shared.poll_changes();
+        assert_eq!(server.take_changes(), vec!["models/kept.mtl".to_string()]);
+        assert!(server.take_changes().is_empty());
+
+        let reloaded = server.reload::<String>("models/kept.mtl", &()).unwrap();
+        assert_eq!(reloaded.as_str(), "new");
+        assert_eq!(kept.as_str(), "old");
+        assert!(server.load::<String>("models/kept.mtl", &()).unwrap().ptr_eq(&reloaded));
+    }
+
+    #[test]
+    fn dropping_a_reloaded_asset_falls_back_to_the_version_in_use() {
+        let dir = asset_dir();
+        std::fs::write(dir.join("shader.wgsl"), "good").unwrap();
+
+        let server = AssetServer::with_workers(1);
+        let in_use = server.load::<String>("shader.wgsl", &()).unwrap();
+
+        // The consumer rejects the edited version, as a renderer does with a shader that fails to compile
+        touch(&dir.join("shader.wgsl"), "broken");
+        server.shared.poll_changes();
+        assert_eq!(server.take_changes(), vec!["shader.wgsl".to_string()]);
+        let rejected = server.reload::<String>("shader.wgsl", &()).unwrap();
+        assert_eq!(rejected.as_str(), "broken");
+        drop(rejected);
+
+        assert!(server.get::<String>("shader.wgsl").unwrap().ptr_eq(&in_use));
+        std::fs::write(dir.join("shader.wgsl"), "fixed").unwrap();
+        let file = std::fs::File::options().write(true).open(dir.join("shader.wgsl")).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(120)).unwrap();
+        server.shared.poll_changes();
+        assert_eq!(server.take_changes(), vec!["shader.wgsl".to_string()]);
+        assert_eq!(server.reload::<String>("shader.wgsl", &()).unwrap().as_str(), "fixed");
+    }
+}
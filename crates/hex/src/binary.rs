@@ -0,0 +1,425 @@
+use std::fmt;
+
+use crate::hexagon::Axial;
+use crate::map::{HexMap, MapShape};
+
+// Layout of an encoded map, all integers little endian:
+//   magic    4 bytes  "BHEX"
+//   version  u8
+//   shape    u8 tag followed by the shape's i32 parameters
+//   count    u32 number of values
+//   values   `count` values in coordinate order, each written by `MapValue::encode`
+const MAGIC: &[u8; 4] = b"BHEX";
+pub const VERSION: u8 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    UnknownShape(u8),
+    InvalidShape(MapShape),
+    UnexpectedEnd,
+    InvalidValue(&'static str),
+    CountMismatch { expected: usize, found: usize },
+    TrailingBytes(usize),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::BadMagic => write!(f, "data is not an encoded hex map"),
+            DecodeError::UnsupportedVersion(v) => write!(f, "unsupported hex map version {v}, expected {VERSION}"),
+            DecodeError::UnknownShape(tag) => write!(f, "unknown map shape tag {tag}"),
+            DecodeError::InvalidShape(shape) => write!(f, "invalid map shape {shape:?}"),
+            DecodeError::UnexpectedEnd => write!(f, "unexpected end of data"),
+            DecodeError::InvalidValue(what) => write!(f, "invalid {what} value"),
+            DecodeError::CountMismatch { expected, found } => write!(f, "map shape holds {expected} values but the data has {found}"),
+            DecodeError::TrailingBytes(n) => write!(f, "{n} unexpected bytes after the end of the map"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncodeError {
+    /// The map holds more values than the `u32` count in the header can describe
+    TooManyValues(usize),
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::TooManyValues(n) => write!(f, "hex map holds {n} values, more than the {} an encoded map can", u32::MAX),
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+/// A value that can be stored in an encoded `HexMap`
+pub trait MapValue: Sized {
+    fn encode(&self, out: &mut Vec<u8>);
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError>;
+}
+
+fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], DecodeError> {
+    if input.len() < len {
+        return Err(DecodeError::UnexpectedEnd);
+    }
+    let (head, tail) = input.split_at(len);
+    *input = tail;
+    Ok(head)
+}
+
+macro_rules! impl_map_value_le {
+    ($($t:ty),*) => {
+        $(
+            impl MapValue for $t {
+                fn encode(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+                    let bytes = take(input, std::mem::size_of::<$t>())?;
+                    Ok(<$t>::from_le_bytes(bytes.try_into().expect("Slice has the size of the type")))
+                }
+            }
+        )*
+    };
+}
+
+// Floats go through `to_le_bytes` as well so every bit, including NaN payloads, round-trips exactly
+impl_map_value_le!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+impl MapValue for bool {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        match u8::decode(input)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(DecodeError::InvalidValue("bool")),
+        }
+    }
+}
+
+impl<T: MapValue> MapValue for Option<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            None => out.push(0),
+            Some(value) => {
+                out.push(1);
+                value.encode(out);
+            },
+        }
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        match u8::decode(input)? {
+            0 => Ok(None),
+            1 => Ok(Some(T::decode(input)?)),
+            _ => Err(DecodeError::InvalidValue("option")),
+        }
+    }
+}
+
+impl<A: MapValue, B: MapValue> MapValue for (A, B) {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.0.encode(out);
+        self.1.encode(out);
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        Ok((A::decode(input)?, B::decode(input)?))
+    }
+}
+
+impl<A: MapValue, B: MapValue, C: MapValue> MapValue for (A, B, C) {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.0.encode(out);
+        self.1.encode(out);
+        self.2.encode(out);
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        Ok((A::decode(input)?, B::decode(input)?, C::decode(input)?))
+    }
+}
+
+impl MapValue for Axial<i32> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.q().encode(out);
+        self.r().encode(out);
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        Ok(Axial::new(i32::decode(input)?, i32::decode(input)?))
+    }
+}
+
+impl MapValue for MapShape {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match *self {
+            MapShape::Hexagon { radius } => {
+                out.push(0);
+                radius.encode(out);
+            },
+            MapShape::Rectangle { width, height } => {
+                out.push(1);
+                (width, height).encode(out);
+            },
+            MapShape::Parallelogram { q_min, q_max, r_min, r_max } => {
+                out.push(2);
+                ((q_min, q_max), (r_min, r_max)).encode(out);
+            },
+            MapShape::Triangle { size } => {
+                out.push(3);
+                size.encode(out);
+            },
+        }
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        let shape = match u8::decode(input)? {
+            0 => MapShape::Hexagon { radius: i32::decode(input)? },
+            1 => {
+                let (width, height) = MapValue::decode(input)?;
+                MapShape::Rectangle { width, height }
+            },
+            2 => {
+                let ((q_min, q_max), (r_min, r_max)) = MapValue::decode(input)?;
+                MapShape::Parallelogram { q_min, q_max, r_min, r_max }
+            },
+            3 => MapShape::Triangle { size: i32::decode(input)? },
+            tag => return Err(DecodeError::UnknownShape(tag)),
+        };
+        Ok(shape)
+    }
+}
+
+fn value_count(len: usize) -> Result<u32, EncodeError> {
+    u32::try_from(len).map_err(|_| EncodeError::TooManyValues(len))
+}
+
+pub fn encode_map<T: MapValue>(map: &HexMap<T>) -> Result<Vec<u8>, EncodeError> {
+    let count = value_count(map.len())?;
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.push(VERSION);
+    map.shape().encode(&mut out);
+    count.encode(&mut out);
+    for value in map.values() {
+        value.encode(&mut out);
+    }
+    Ok(out)
+}
+
+pub fn decode_map<T: MapValue>(mut input: &[u8]) -> Result<HexMap<T>, DecodeError> {
+    if take(&mut input, MAGIC.len()).map_err(|_| DecodeError::BadMagic)? != MAGIC {
+        return Err(DecodeError::BadMagic);
+    }
+
+    let version = u8::decode(&mut input)?;
+    if version != VERSION {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+
+    let shape = MapShape::decode(&mut input)?;
+    let expected = shape.hex_count().ok_or(DecodeError::InvalidShape(shape))?;
+    let found = u32::decode(&mut input)? as usize;
+    if found != expected {
+        return Err(DecodeError::CountMismatch { expected, found });
+    }
+
+    // Every value takes at least one byte, so the remaining input bounds how much is worth reserving
+    let mut values = Vec::with_capacity(found.min(input.len()));
+    for _ in 0..found {
+        values.push(T::decode(&mut input)?);
+    }
+    if !input.is_empty() {
+        return Err(DecodeError::TrailingBytes(input.len()));
+    }
+
+    Ok(HexMap::from_values(shape, values).expect("Value count was checked against the shape"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Debug;
+    use std::time::{Duration, Instant};
+
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn shape() -> impl Strategy<Value = MapShape> {
+        prop_oneof![
+            (0..8).prop_map(|radius| MapShape::Hexagon { radius }),
+            (0..10, 0..10).prop_map(|(width, height)| MapShape::Rectangle { width, height }),
+            (-6..6, 0..6, -6..6, 0..6).prop_map(|(q_min, q_len, r_min, r_len)| {
+                MapShape::Parallelogram { q_min, q_max: q_min + q_len, r_min, r_max: r_min + r_len }
+            }),
+            (0..8).prop_map(|size| MapShape::Triangle { size }),
+        ]
+    }
+
+    fn map<T: Debug>(value: impl Strategy<Value = T> + Clone) -> impl Strategy<Value = HexMap<T>> {
+        shape().prop_flat_map(move |shape| {
+            let len = shape.hex_count().unwrap();
+            prop::collection::vec(value.clone(), len).prop_map(move |values| HexMap::from_values(shape, values).unwrap())
+        })
+    }
+
+    /// Floats are compared by their bits so that NaN payloads have to survive as well
+    fn f32_bits() -> impl Strategy<Value = f32> + Clone {
+        prop_oneof![any::<u32>().prop_map(f32::from_bits), Just(f32::NAN), Just(-0.0f32), Just(f32::INFINITY)]
+    }
+
+    fn f64_bits() -> impl Strategy<Value = f64> + Clone {
+        prop_oneof![any::<u64>().prop_map(f64::from_bits), Just(f64::NAN), Just(-0.0f64), Just(f64::NEG_INFINITY)]
+    }
+
+    fn round_trip<T: MapValue, K: PartialEq + Debug>(map: &HexMap<T>, key: impl Fn(&T) -> K) -> Result<(), TestCaseError> {
+        let decoded = decode_map::<T>(&encode_map(map).unwrap()).map_err(|e| TestCaseError::fail(e.to_string()))?;
+        prop_assert_eq!(decoded.shape(), map.shape());
+        prop_assert_eq!(decoded.values().map(&key).collect::<Vec<_>>(), map.values().map(&key).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    fn header(shape: MapShape, count: u32) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.push(VERSION);
+        shape.encode(&mut out);
+        count.encode(&mut out);
+        out
+    }
+
+    proptest! {
+        #[test]
+        fn round_trips_integers(map in map(any::<u8>())) {
+            round_trip(&map, |v| *v)?;
+        }
+
+        #[test]
+        fn round_trips_wide_integers(map in map(any::<(i64, u16)>())) {
+            round_trip(&map, |v| *v)?;
+        }
+
+        #[test]
+        fn round_trips_f32(map in map(f32_bits())) {
+            round_trip(&map, |v| v.to_bits())?;
+        }
+
+        #[test]
+        fn round_trips_f64(map in map(f64_bits())) {
+            round_trip(&map, |v| v.to_bits())?;
+        }
+
+        #[test]
+        fn round_trips_options_and_tuples(map in map(any::<(Option<i32>, bool, Option<u64>)>())) {
+            round_trip(&map, |v| *v)?;
+        }
+
+        #[test]
+        fn round_trips_axials(map in map(any::<(i32, i32)>().prop_map(|(q, r)| Axial::new(q, r)))) {
+            round_trip(&map, |v| *v)?;
+        }
+
+        #[test]
+        fn rejects_truncated_input(map in map(any::<u16>()), cut in any::<prop::sample::Index>()) {
+            let encoded = encode_map(&map).unwrap();
+            let len = cut.index(encoded.len());
+            prop_assert!(decode_map::<u16>(&encoded[..len]).is_err());
+        }
+
+        #[test]
+        fn rejects_trailing_bytes(map in map(any::<i32>()), extra in prop::collection::vec(any::<u8>(), 1..8)) {
+            let mut encoded = encode_map(&map).unwrap();
+            encoded.extend_from_slice(&extra);
+            prop_assert_eq!(decode_map::<i32>(&encoded).unwrap_err(), DecodeError::TrailingBytes(extra.len()));
+        }
+
+        #[test]
+        fn rejects_bad_magic(map in map(any::<u8>()), index in 0..4usize, flip in 1..=255u8) {
+            let mut encoded = encode_map(&map).unwrap();
+            encoded[index] ^= flip;
+            prop_assert_eq!(decode_map::<u8>(&encoded).unwrap_err(), DecodeError::BadMagic);
+        }
+
+        #[test]
+        fn rejects_other_versions(map in map(any::<u8>()), version in any::<u8>().prop_filter("other version", |v| *v != VERSION)) {
+            let mut encoded = encode_map(&map).unwrap();
+            encoded[MAGIC.len()] = version;
+            prop_assert_eq!(decode_map::<u8>(&encoded).unwrap_err(), DecodeError::UnsupportedVersion(version));
+        }
+
+        #[test]
+        fn never_panics_on_garbage(mut data in prop::collection::vec(any::<u8>(), 0..64), tag in 0..5u8) {
+            // A valid prefix gets the garbage past the magic and version checks
+            let mut input = MAGIC.to_vec();
+            input.push(VERSION);
+            input.push(tag);
+            input.append(&mut data);
+            let _ = decode_map::<u32>(&input);
+        }
+    }
+
+    #[test]
+    fn rejects_empty_and_short_input() {
+        assert_eq!(decode_map::<u8>(&[]).unwrap_err(), DecodeError::BadMagic);
+        assert_eq!(decode_map::<u8>(b"BH").unwrap_err(), DecodeError::BadMagic);
+        assert_eq!(decode_map::<u8>(MAGIC).unwrap_err(), DecodeError::UnexpectedEnd);
+    }
+
+    #[test]
+    fn rejects_invalid_shapes_quickly() {
+        let shapes = [
+            MapShape::Hexagon { radius: i32::MAX },
+            MapShape::Hexagon { radius: -1 },
+            MapShape::Rectangle { width: 1, height: i32::MIN },
+            MapShape::Triangle { size: i32::MIN },
+        ];
+        for shape in shapes {
+            assert_eq!(decode_map::<u8>(&header(shape, 0)).unwrap_err(), DecodeError::InvalidShape(shape));
+        }
+
+        // Huge but valid shapes fail on the count without iterating their hexes
+        let start = Instant::now();
+        let huge = MapShape::Parallelogram { q_min: i32::MIN, q_max: i32::MAX, r_min: i32::MIN, r_max: i32::MAX };
+        assert_eq!(decode_map::<u8>(&header(huge, 1)).unwrap_err(), DecodeError::InvalidShape(huge));
+        let wide = MapShape::Parallelogram { q_min: i32::MIN, q_max: i32::MAX, r_min: 0, r_max: 0 };
+        assert_eq!(decode_map::<u8>(&header(wide, 1)).unwrap_err(), DecodeError::CountMismatch { expected: 1 << 32, found: 1 });
+        let tall = MapShape::Parallelogram { q_min: 0, q_max: 0, r_min: 0, r_max: i32::MAX - 1 };
+        assert_eq!(decode_map::<u8>(&header(tall, i32::MAX as u32)).unwrap_err(), DecodeError::UnexpectedEnd);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn counts_that_do_not_fit_the_header_fail_to_encode() {
+        assert_eq!(value_count(u32::MAX as usize), Ok(u32::MAX));
+        if let Ok(len) = usize::try_from(u32::MAX as u64 + 1) {
+            assert_eq!(value_count(len), Err(EncodeError::TooManyValues(len)));
+        }
+    }
+
+    #[test]
+    fn rejects_bad_values() {
+        let map = HexMap::filled(MapShape::Triangle { size: 1 }, true);
+        let mut encoded = encode_map(&map).unwrap();
+        *encoded.last_mut().unwrap() = 2;
+        assert_eq!(decode_map::<bool>(&encoded).unwrap_err(), DecodeError::InvalidValue("bool"));
+        assert_eq!(decode_map::<u8>(&[b'B', b'H', b'E', b'X', VERSION, 9]).unwrap_err(), DecodeError::UnknownShape(9));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_rejects_invalid_shapes() {
+        let json = r#"{"shape":{"Hexagon":{"radius":2147483647}},"values":[]}"#;
+        assert!(serde_json::from_str::<HexMap<u8>>(json).unwrap_err().to_string().contains("invalid HexMap shape"));
+        let json = r#"{"shape":{"Triangle":{"size":1}},"values":[1,2]}"#;
+        assert!(serde_json::from_str::<HexMap<u8>>(json).is_err());
+    }
+}
@@ -0,0 +1,155 @@
+use crate::hexagon::{self, Axial, Direction, HexRange};
+use crate::map::MapShape;
+
+/// The position of a chunk in chunk space. Chunks form their own hex grid, so adjacent chunks are one axial step apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChunkCoord(pub Axial<i32>);
+
+impl ChunkCoord {
+    pub fn new(q: i32, r: i32) -> Self {
+        ChunkCoord(Axial::new(q, r))
+    }
+
+    pub fn neighbor(&self, direction: Direction) -> Self {
+        ChunkCoord(self.0.neighbor(direction))
+    }
+
+    pub fn neighbors(&self) -> [Self; 6] {
+        self.0.neighbors().map(ChunkCoord)
+    }
+
+    /// Distance in chunk steps
+    pub fn distance(&self, other: &Self) -> i32 {
+        self.0.distance(&other.0)
+    }
+}
+
+/// Partitions the infinite axial plane into hexagonal super-hexes, each holding every tile within `radius` of its center.
+/// A chunk holds `3 * radius * (radius + 1) + 1` tiles and its neighbors' centers are `2 * radius + 1` tiles away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChunkLayout {
+    radius: i32,
+}
+
+impl ChunkLayout {
+    pub fn new(radius: i32) -> Self {
+        assert!(radius >= 0, "Chunk radius must not be negative");
+        ChunkLayout { radius }
+    }
+
+    #[inline]
+    pub fn radius(&self) -> i32 {
+        self.radius
+    }
+
+    /// The number of tiles in every chunk
+    #[inline]
+    pub fn tile_count(&self) -> usize {
+        let radius = self.radius as usize;
+        3 * radius * (radius + 1) + 1
+    }
+
+    /// The shape of a `HexMap` that stores one chunk's tiles by their local offset from the chunk center
+    pub fn local_shape(&self) -> MapShape {
+        MapShape::Hexagon { radius: self.radius }
+    }
+
+    /// The tile at the center of `chunk`. Chunk space steps along `(2R + 1, -R)` for `q` and `(R, R + 1)` for `r`.
+    pub fn center(&self, chunk: ChunkCoord) -> Axial<i32> {
+        let n = self.radius;
+        let (cq, cr) = (chunk.0.q(), chunk.0.r());
+        Axial::new((2 * n + 1) * cq + n * cr, -n * cq + (n + 1) * cr)
+    }
+
+    pub fn chunk_of(&self, tile: Axial<i32>) -> ChunkCoord {
+        let n = self.radius;
+        let area = 3 * n * n + 3 * n + 1;
+        let (q, r) = (tile.q(), tile.r());
+
+        // Invert the chunk basis to find the lattice cell the tile lies in,
+        // then pick whichever nearby center the tile is within `radius` of
+        let cq = ((n + 1) * q - n * r).div_euclid(area);
+        let cr = (n * q + (2 * n + 1) * r).div_euclid(area);
+
+        (cq - 1..=cq + 1)
+            .flat_map(|q| (cr - 1..=cr + 1).map(move |r| ChunkCoord::new(q, r)))
+            .find(|&chunk| self.center(chunk).distance(&tile) <= n)
+            .expect("Every tile lies within radius of exactly one chunk center")
+    }
+
+    /// Splits a tile into its chunk and its offset from that chunk's center
+    pub fn to_local(&self, tile: Axial<i32>) -> (ChunkCoord, Axial<i32>) {
+        let chunk = self.chunk_of(tile);
+        (chunk, tile - self.center(chunk))
+    }
+
+    pub fn to_tile(&self, chunk: ChunkCoord, local: Axial<i32>) -> Axial<i32> {
+        self.center(chunk) + local
+    }
+
+    /// Iterates every tile in `chunk`
+    pub fn tiles(&self, chunk: ChunkCoord) -> HexRange {
+        hexagon::range(self.center(chunk), self.radius)
+    }
+
+    /// Iterates every chunk within `radius` chunk steps of `chunk`, such as the set to keep loaded around a viewer
+    pub fn chunks_in_range(&self, chunk: ChunkCoord, radius: i32) -> impl Iterator<Item = ChunkCoord> {
+        hexagon::range(chunk.0, radius).map(ChunkCoord)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    const RADII: [i32; 5] = [0, 1, 2, 3, 7];
+
+    #[test]
+    fn local_round_trips() {
+        for radius in RADII {
+            let layout = ChunkLayout::new(radius);
+            for tile in hexagon::range(Axial::new(-11, 23), 30) {
+                let (chunk, local) = layout.to_local(tile);
+                assert_eq!(chunk, layout.chunk_of(tile));
+                assert!(layout.local_shape().contains(local), "{tile:?} is {local:?} from the center of {chunk:?}");
+                assert_eq!(layout.to_tile(chunk, local), tile, "radius {radius}");
+            }
+        }
+    }
+
+    #[test]
+    fn every_tile_in_a_chunk_maps_back_to_it() {
+        for radius in RADII {
+            let layout = ChunkLayout::new(radius);
+            let mut seen = HashSet::new();
+            let chunks = layout.chunks_in_range(ChunkCoord::new(2, -1), 3).collect::<Vec<_>>();
+
+            for &chunk in &chunks {
+                let center = layout.center(chunk);
+                assert_eq!(layout.to_local(center), (chunk, Axial::new(0, 0)));
+                assert_eq!(layout.tiles(chunk).len(), layout.tile_count());
+                for tile in layout.tiles(chunk) {
+                    assert_eq!(layout.chunk_of(tile), chunk, "radius {radius}");
+                    assert!(seen.insert(tile), "{tile:?} is in more than one chunk");
+                }
+            }
+            assert_eq!(seen.len(), chunks.len() * layout.tile_count());
+        }
+    }
+
+    #[test]
+    fn neighboring_chunks_are_one_chunk_width_apart() {
+        for radius in RADII {
+            let layout = ChunkLayout::new(radius);
+            let chunk = ChunkCoord::new(-4, 3);
+            for neighbor in chunk.neighbors() {
+                assert_eq!(chunk.distance(&neighbor), 1);
+                assert_eq!(layout.center(chunk).distance(&layout.center(neighbor)), 2 * radius + 1);
+            }
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use crate::hexagon::{Axial, Cube, HexNum};
+use crate::layout::Layout;
+
+/// A hex position with fractional cube components, such as a world position that falls between hex centers.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FractionalHex {
+    q: f32,
+    r: f32,
+    s: f32,
+}
+
+impl FractionalHex {
+    pub fn new(q: f32, r: f32, s: f32) -> Self {
+        FractionalHex { q, r, s }
+    }
+
+    /// Converts a world space XZ position into a fractional hex, inverting `Axial::to_cartesian` scaled by `size`
+    pub fn from_cartesian(x: f32, z: f32, size: f32) -> Self {
+        Layout::pointy(size).world_to_hex(x, z)
+    }
+
+    #[inline]
+    pub fn q(&self) -> f32 {
+        self.q
+    }
+
+    #[inline]
+    pub fn r(&self) -> f32 {
+        self.r
+    }
+
+    #[inline]
+    pub fn s(&self) -> f32 {
+        self.s
+    }
+
+    pub fn to_axial(&self) -> Axial<f32> {
+        Axial::new(self.q, self.r)
+    }
+
+    pub fn to_cube(&self) -> Cube<f32> {
+        Cube::new(self.q, self.r, -self.q - self.r)
+    }
+
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        FractionalHex {
+            q: self.q + (other.q - self.q) * t,
+            r: self.r + (other.r - self.r) * t,
+            s: self.s + (other.s - self.s) * t,
+        }
+    }
+
+    /// Rounds to the nearest integer hex. The component with the largest rounding error
+    /// is recomputed from the other two so the result keeps `q + r + s == 0`.
+    pub fn round(&self) -> Axial<i32> {
+        let mut q = self.q.round();
+        let mut r = self.r.round();
+        let s = self.s.round();
+
+        let q_diff = (q - self.q).abs();
+        let r_diff = (r - self.r).abs();
+        let s_diff = (s - self.s).abs();
+
+        if q_diff > r_diff && q_diff > s_diff {
+            q = -r - s;
+        } else if r_diff > s_diff {
+            r = -q - s;
+        }
+
+        Axial::new(q as i32, r as i32)
+    }
+}
+
+impl<T: HexNum> From<Axial<T>> for FractionalHex {
+    fn from(value: Axial<T>) -> Self {
+        let q = value.q().to_f32().unwrap_or_default();
+        let r = value.r().to_f32().unwrap_or_default();
+        FractionalHex { q, r, s: -q - r }
+    }
+}
+
+impl<T: HexNum> From<Cube<T>> for FractionalHex {
+    fn from(value: Cube<T>) -> Self {
+        value.to_axial().into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hexagon::range;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{a} != {b}");
+    }
+
+    #[test]
+    fn round_exact_hex_centers() {
+        for hex in range(Axial::new(0, 0), 5) {
+            assert_eq!(FractionalHex::from(hex).round(), hex);
+        }
+    }
+
+    #[test]
+    fn round_edge_midpoints() {
+        // Halfway between two neighbors the result must be one of them and keep q + r + s == 0
+        for hex in range(Axial::new(0, 0), 3) {
+            for neighbor in hex.neighbors() {
+                let midpoint = FractionalHex::from(hex).lerp(&FractionalHex::from(neighbor), 0.5);
+                let rounded = midpoint.round();
+                assert!(rounded == hex || rounded == neighbor, "{midpoint:?} rounded to {rounded:?}");
+                assert_eq!(rounded.q() + rounded.r() + rounded.s(), 0);
+            }
+        }
+
+        assert_eq!(FractionalHex::new(0.5, -0.5, 0.0).round(), Axial::new(1, -1));
+        assert_eq!(FractionalHex::new(-0.5, 0.5, 0.0).round(), Axial::new(-1, 1));
+        assert_eq!(FractionalHex::new(0.0, 0.5, -0.5).round(), Axial::new(0, 1));
+    }
+
+    #[test]
+    fn round_near_vertex() {
+        // A corner shared by three hexes, nudged towards each of them
+        let corner = FractionalHex::new(1.0 / 3.0, -2.0 / 3.0, 1.0 / 3.0);
+        for hex in [Axial::new(0, 0), Axial::new(1, -1), Axial::new(0, -1)] {
+            let nudged = corner.lerp(&FractionalHex::from(hex), 0.01);
+            assert_eq!(nudged.round(), hex);
+        }
+    }
+
+    #[test]
+    fn lerp_endpoints() {
+        let a = FractionalHex::new(1.5, -2.0, 0.5);
+        let b = FractionalHex::from(Axial::new(-4, 7));
+        assert_eq!(a.lerp(&b, 0.0), a);
+        assert_eq!(a.lerp(&b, 1.0), b);
+        assert_eq!(a.lerp(&a, 0.37), a);
+    }
+
+    #[test]
+    fn cartesian_round_trip() {
+        for hex in range(Axial::new(0, 0), 6).chain([Axial::new(120, -45)]) {
+            let (x, z) = hex.to_cartesian();
+            let back = FractionalHex::from_cartesian(x, z, 1.0);
+            assert_close(back.q(), hex.q() as f32);
+            assert_close(back.r(), hex.r() as f32);
+            assert_close(back.s(), hex.s() as f32);
+            assert_eq!(back.round(), hex);
+        }
+    }
+}
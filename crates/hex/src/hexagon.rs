@@ -1,20 +1,92 @@
 use std::ops;
-use std::ops::Neg;
 
 use num_traits::Num;
+use num_traits::Signed;
 use num_traits::ToPrimitive;
 
-const SQRT_3: f32 = 1.73205080757;
-const HALF_SQRT_3: f32 = SQRT_3 / 2.0;
+use crate::layout::Layout;
 
-pub trait HexNum: Num + ToPrimitive + Copy + ops::Neg<Output = Self> {}
-impl<T> HexNum for T where T: Num + ToPrimitive + Copy + ops::Neg<Output = Self> {}
+pub trait HexNum: Num + Signed + ToPrimitive + Copy + ops::Neg<Output = Self> {}
+impl<T> HexNum for T where T: Num + Signed + ToPrimitive + Copy + ops::Neg<Output = Self> {}
 
+/// The six edge directions of a pointy-top hexagon, ordered counter-clockwise starting from east.
+/// North points towards negative `r` (negative Z in world space).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Direction {
+    East,
+    NorthEast,
+    NorthWest,
+    West,
+    SouthWest,
+    SouthEast,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 6] = [
+        Direction::East,
+        Direction::NorthEast,
+        Direction::NorthWest,
+        Direction::West,
+        Direction::SouthWest,
+        Direction::SouthEast,
+    ];
+
+    #[inline]
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Returns the direction for the given index, wrapping around every six steps
+    #[inline]
+    pub fn from_index(index: usize) -> Self {
+        Self::ALL[index % 6]
+    }
+
+    #[inline]
+    pub fn rotate_ccw(self) -> Self {
+        Self::from_index(self.index() + 1)
+    }
+
+    #[inline]
+    pub fn rotate_cw(self) -> Self {
+        Self::from_index(self.index() + 5)
+    }
+
+    #[inline]
+    pub fn opposite(self) -> Self {
+        Self::from_index(self.index() + 3)
+    }
+
+    /// The unit offset of a single step in this direction
+    pub fn to_axial<T: HexNum>(self) -> Axial<T> {
+        let one = T::one();
+        let zero = T::zero();
+        match self {
+            Direction::East => Axial::new(one, zero),
+            Direction::NorthEast => Axial::new(one, -one),
+            Direction::NorthWest => Axial::new(zero, -one),
+            Direction::West => Axial::new(-one, zero),
+            Direction::SouthWest => Axial::new(-one, one),
+            Direction::SouthEast => Axial::new(zero, one),
+        }
+    }
+
+    /// The offset to the diagonal neighbor lying between this direction and the next counter-clockwise one
+    pub fn to_diagonal_axial<T: HexNum>(self) -> Axial<T> {
+        self.to_axial() + self.rotate_ccw().to_axial()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Axial<T: HexNum> {
     q: T,
     r: T,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Cube<T: HexNum> {
     q: T,
     r: T,
@@ -26,16 +98,190 @@ impl<T: HexNum> Axial<T> {
         Axial { q, r }
     }
 
+    #[inline]
+    pub fn q(&self) -> T {
+        self.q
+    }
+
+    #[inline]
+    pub fn r(&self) -> T {
+        self.r
+    }
+
+    /// The implicit third cube coordinate, derived from `q + r + s == 0`
+    #[inline]
+    pub fn s(&self) -> T {
+        -self.q - self.r
+    }
+
     pub fn to_cube(&self) -> Cube<T> {
-        Cube { q: self.q, r: self.r, s: -self.q - self.r }
+        Cube { q: self.q, r: self.r, s: self.s() }
+    }
+
+    pub fn neighbor(&self, direction: Direction) -> Self {
+        *self + direction.to_axial()
+    }
+
+    pub fn neighbors(&self) -> [Self; 6] {
+        Direction::ALL.map(|d| self.neighbor(d))
+    }
+
+    pub fn diagonal_neighbor(&self, direction: Direction) -> Self {
+        *self + direction.to_diagonal_axial()
+    }
+
+    pub fn diagonal_neighbors(&self) -> [Self; 6] {
+        Direction::ALL.map(|d| self.diagonal_neighbor(d))
+    }
+
+    /// Distance in hex steps from the origin
+    pub fn length(&self) -> T {
+        self.to_cube().length()
     }
 
+    pub fn distance(&self, other: &Self) -> T {
+        (*self - *other).length()
+    }
+
+    /// Rotates around the origin by 60 degrees counter-clockwise
+    pub fn rotate_ccw(&self) -> Self {
+        self.to_cube().rotate_ccw().to_axial()
+    }
+
+    /// Rotates around the origin by 60 degrees clockwise
+    pub fn rotate_cw(&self) -> Self {
+        self.to_cube().rotate_cw().to_axial()
+    }
+
+    /// Rotates around the origin by `steps` multiples of 60 degrees, positive steps turning counter-clockwise
+    pub fn rotate(&self, steps: i32) -> Self {
+        self.to_cube().rotate(steps).to_axial()
+    }
+
+    pub fn rotate_around(&self, center: &Self, steps: i32) -> Self {
+        (*self - *center).rotate(steps) + *center
+    }
+
+    /// Mirrors across the `q` axis, keeping `q` and swapping `r` and `s`
+    pub fn reflect_q(&self) -> Self {
+        self.to_cube().reflect_q().to_axial()
+    }
+
+    /// Mirrors across the `r` axis, keeping `r` and swapping `q` and `s`
+    pub fn reflect_r(&self) -> Self {
+        self.to_cube().reflect_r().to_axial()
+    }
+
+    /// Mirrors across the `s` axis, keeping `s` and swapping `q` and `r`
+    pub fn reflect_s(&self) -> Self {
+        self.to_cube().reflect_s().to_axial()
+    }
+
+    /// World XZ position of the hex center using the default unit sized, pointy-top `Layout`
     pub fn to_cartesian(&self) -> (f32, f32) {
+        Layout::default().hex_to_world(*self)
+    }
+}
 
-        let q = self.q.to_f32().unwrap_or_default();
-        let r = self.r.to_f32().unwrap_or_default();
+impl<T: HexNum> Cube<T> {
+    /// Creates a new cube coordinate. The components must satisfy `q + r + s == 0`.
+    pub fn new(q: T, r: T, s: T) -> Self {
+        debug_assert!(q + r + s == T::zero(), "Cube coordinates must satisfy q + r + s == 0");
+        Cube { q, r, s }
+    }
 
-        (SQRT_3 * q + HALF_SQRT_3 * r, 1.5 * r)
+    #[inline]
+    pub fn q(&self) -> T {
+        self.q
+    }
+
+    #[inline]
+    pub fn r(&self) -> T {
+        self.r
+    }
+
+    #[inline]
+    pub fn s(&self) -> T {
+        self.s
+    }
+
+    pub fn to_axial(&self) -> Axial<T> {
+        Axial { q: self.q, r: self.r }
+    }
+
+    pub fn neighbor(&self, direction: Direction) -> Self {
+        *self + direction.to_axial().to_cube()
+    }
+
+    pub fn neighbors(&self) -> [Self; 6] {
+        Direction::ALL.map(|d| self.neighbor(d))
+    }
+
+    pub fn diagonal_neighbor(&self, direction: Direction) -> Self {
+        *self + direction.to_diagonal_axial().to_cube()
+    }
+
+    pub fn diagonal_neighbors(&self) -> [Self; 6] {
+        Direction::ALL.map(|d| self.diagonal_neighbor(d))
+    }
+
+    /// Distance in hex steps from the origin
+    pub fn length(&self) -> T {
+        (self.q.abs() + self.r.abs() + self.s.abs()) / (T::one() + T::one())
+    }
+
+    pub fn distance(&self, other: &Self) -> T {
+        (*self - *other).length()
+    }
+
+    /// Rotates around the origin by 60 degrees counter-clockwise
+    pub fn rotate_ccw(&self) -> Self {
+        Cube { q: -self.s, r: -self.q, s: -self.r }
+    }
+
+    /// Rotates around the origin by 60 degrees clockwise
+    pub fn rotate_cw(&self) -> Self {
+        Cube { q: -self.r, r: -self.s, s: -self.q }
+    }
+
+    /// Rotates around the origin by `steps` multiples of 60 degrees, positive steps turning counter-clockwise
+    pub fn rotate(&self, steps: i32) -> Self {
+        (0..steps.rem_euclid(6)).fold(*self, |hex, _| hex.rotate_ccw())
+    }
+
+    pub fn rotate_around(&self, center: &Self, steps: i32) -> Self {
+        (*self - *center).rotate(steps) + *center
+    }
+
+    /// Mirrors across the `q` axis, keeping `q` and swapping `r` and `s`
+    pub fn reflect_q(&self) -> Self {
+        Cube { q: self.q, r: self.s, s: self.r }
+    }
+
+    /// Mirrors across the `r` axis, keeping `r` and swapping `q` and `s`
+    pub fn reflect_r(&self) -> Self {
+        Cube { q: self.s, r: self.r, s: self.q }
+    }
+
+    /// Mirrors across the `s` axis, keeping `s` and swapping `q` and `r`
+    pub fn reflect_s(&self) -> Self {
+        Cube { q: self.r, r: self.q, s: self.s }
+    }
+
+    pub fn to_cartesian(&self) -> (f32, f32) {
+        self.to_axial().to_cartesian()
+    }
+}
+
+impl<T: HexNum> From<Cube<T>> for Axial<T> {
+    fn from(value: Cube<T>) -> Self {
+        value.to_axial()
+    }
+}
+
+impl<T: HexNum> From<Axial<T>> for Cube<T> {
+    fn from(value: Axial<T>) -> Self {
+        value.to_cube()
     }
 }
 
@@ -52,4 +298,324 @@ impl<T: HexNum> ops::AddAssign for Axial<T> {
         self.q = self.q + rhs.q;
         self.r = self.r + rhs.r;
     }
-}
\ No newline at end of file
+}
+
+impl<T: HexNum> ops::Sub for Axial<T> {
+    type Output = Axial<T>;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Axial { q: self.q - rhs.q, r: self.r - rhs.r }
+    }
+}
+
+impl<T: HexNum> ops::SubAssign for Axial<T> {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.q = self.q - rhs.q;
+        self.r = self.r - rhs.r;
+    }
+}
+
+impl<T: HexNum> ops::Neg for Axial<T> {
+    type Output = Axial<T>;
+
+    fn neg(self) -> Self::Output {
+        Axial { q: -self.q, r: -self.r }
+    }
+}
+
+impl<T: HexNum> ops::Mul<T> for Axial<T> {
+    type Output = Axial<T>;
+
+    fn mul(self, rhs: T) -> Self::Output {
+        Axial { q: self.q * rhs, r: self.r * rhs }
+    }
+}
+
+impl<T: HexNum> ops::MulAssign<T> for Axial<T> {
+    fn mul_assign(&mut self, rhs: T) {
+        self.q = self.q * rhs;
+        self.r = self.r * rhs;
+    }
+}
+
+impl<T: HexNum> ops::Add for Cube<T> {
+    type Output = Cube<T>;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Cube { q: self.q + rhs.q, r: self.r + rhs.r, s: self.s + rhs.s }
+    }
+}
+
+impl<T: HexNum> ops::AddAssign for Cube<T> {
+    fn add_assign(&mut self, rhs: Self) {
+        self.q = self.q + rhs.q;
+        self.r = self.r + rhs.r;
+        self.s = self.s + rhs.s;
+    }
+}
+
+impl<T: HexNum> ops::Sub for Cube<T> {
+    type Output = Cube<T>;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Cube { q: self.q - rhs.q, r: self.r - rhs.r, s: self.s - rhs.s }
+    }
+}
+
+impl<T: HexNum> ops::SubAssign for Cube<T> {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.q = self.q - rhs.q;
+        self.r = self.r - rhs.r;
+        self.s = self.s - rhs.s;
+    }
+}
+
+impl<T: HexNum> ops::Neg for Cube<T> {
+    type Output = Cube<T>;
+
+    fn neg(self) -> Self::Output {
+        Cube { q: -self.q, r: -self.r, s: -self.s }
+    }
+}
+
+impl<T: HexNum> ops::Mul<T> for Cube<T> {
+    type Output = Cube<T>;
+
+    fn mul(self, rhs: T) -> Self::Output {
+        Cube { q: self.q * rhs, r: self.r * rhs, s: self.s * rhs }
+    }
+}
+
+impl<T: HexNum> ops::MulAssign<T> for Cube<T> {
+    fn mul_assign(&mut self, rhs: T) {
+        self.q = self.q * rhs;
+        self.r = self.r * rhs;
+        self.s = self.s * rhs;
+    }
+}
+
+/// Iterates every hex within `radius` steps of `center`, ordered by `q` and then `r`
+pub fn range(center: Axial<i32>, radius: i32) -> HexRange {
+    let radius = radius.max(0);
+    let mut range = HexRange { center, radius, q: -radius, r: 0, remaining: range_count(radius) };
+    range.r = range.r_start();
+    range
+}
+
+/// Iterates the hexes exactly `radius` steps from `center`, counter-clockwise starting from the south west corner
+pub fn ring(center: Axial<i32>, radius: i32) -> HexRing {
+    let radius = radius.max(0);
+    HexRing {
+        current: center + Direction::SouthWest.to_axial() * radius,
+        radius,
+        side: 0,
+        step: 0,
+        remaining: if radius == 0 { 1 } else { 6 * radius as usize },
+    }
+}
+
+/// Iterates `center` followed by each ring out to `radius`, innermost first
+pub fn spiral(center: Axial<i32>, radius: i32) -> HexSpiral {
+    HexSpiral { center, radius: radius.max(0), ring: ring(center, 0) }
+}
+
+fn range_count(radius: i32) -> usize {
+    let radius = radius.max(0) as usize;
+    3 * radius * (radius + 1) + 1
+}
+
+#[derive(Debug, Clone)]
+pub struct HexRange {
+    center: Axial<i32>,
+    radius: i32,
+    q: i32,
+    r: i32,
+    remaining: usize,
+}
+
+impl HexRange {
+    fn r_start(&self) -> i32 {
+        (-self.radius).max(-self.q - self.radius)
+    }
+
+    fn r_end(&self) -> i32 {
+        self.radius.min(-self.q + self.radius)
+    }
+}
+
+impl Iterator for HexRange {
+    type Item = Axial<i32>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let hex = self.center + Axial::new(self.q, self.r);
+        self.remaining -= 1;
+
+        self.r += 1;
+        if self.r > self.r_end() {
+            self.q += 1;
+            self.r = self.r_start();
+        }
+
+        Some(hex)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for HexRange {}
+
+#[derive(Debug, Clone)]
+pub struct HexRing {
+    current: Axial<i32>,
+    radius: i32,
+    side: usize,
+    step: i32,
+    remaining: usize,
+}
+
+impl Iterator for HexRing {
+    type Item = Axial<i32>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let hex = self.current;
+        self.remaining -= 1;
+
+        if self.radius > 0 {
+            self.current = self.current.neighbor(Direction::from_index(self.side));
+            self.step += 1;
+            if self.step == self.radius {
+                self.step = 0;
+                self.side += 1;
+            }
+        }
+
+        Some(hex)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for HexRing {}
+
+#[derive(Debug, Clone)]
+pub struct HexSpiral {
+    center: Axial<i32>,
+    radius: i32,
+    ring: HexRing,
+}
+
+impl Iterator for HexSpiral {
+    type Item = Axial<i32>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(hex) = self.ring.next() {
+                return Some(hex);
+            }
+
+            if self.ring.radius >= self.radius {
+                return None;
+            }
+
+            self.ring = ring(self.center, self.ring.radius + 1);
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.ring.remaining + range_count(self.radius) - range_count(self.ring.radius);
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for HexSpiral {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<Axial<i32>> {
+        range(Axial::new(0, 0), 4).chain([Axial::new(1000, -37), Axial::new(-512, 2048)]).collect()
+    }
+
+    fn balanced<T: HexNum>(hex: Cube<T>) -> bool {
+        hex.q() + hex.r() + hex.s() == T::zero()
+    }
+
+    #[test]
+    fn axial_cube_round_trip() {
+        for hex in sample() {
+            let cube = hex.to_cube();
+            assert!(balanced(cube));
+            assert_eq!(cube.to_axial(), hex);
+            assert_eq!(cube.s(), hex.s());
+        }
+    }
+
+    #[test]
+    fn from_conversions_match_methods() {
+        for hex in sample() {
+            let cube: Cube<i32> = hex.into();
+            assert_eq!(cube, hex.to_cube());
+            assert_eq!(Axial::from(cube), hex);
+        }
+
+        let cube: Cube<f32> = Axial::new(1.5, -0.25).into();
+        assert!(balanced(cube));
+        assert_eq!(cube.s(), -1.25);
+    }
+
+    #[test]
+    fn arithmetic_keeps_invariant() {
+        for a in sample() {
+            for b in [Axial::new(3, -1), Axial::new(-7, 2), Axial::new(0, 0)] {
+                let (ca, cb) = (a.to_cube(), b.to_cube());
+
+                assert!(balanced(ca + cb));
+                assert!(balanced(ca - cb));
+                assert!(balanced(-ca));
+                assert!(balanced(ca * 3));
+                assert!(balanced(ca * -2));
+
+                assert_eq!((a + b).to_cube(), ca + cb);
+                assert_eq!((a - b).to_cube(), ca - cb);
+                assert_eq!((-a).to_cube(), -ca);
+                assert_eq!((a * 3).to_cube(), ca * 3);
+
+                let mut sum = ca;
+                sum += cb;
+                sum -= cb * 2;
+                sum *= -4;
+                assert!(balanced(sum));
+
+                let mut axial = a;
+                axial += b;
+                axial -= b * 2;
+                axial *= -4;
+                assert_eq!(axial.to_cube(), sum);
+            }
+        }
+    }
+
+    #[test]
+    fn neighbors_and_rotations_keep_invariant() {
+        for hex in sample() {
+            let cube = hex.to_cube();
+            assert!(cube.neighbors().into_iter().chain(cube.diagonal_neighbors()).all(balanced));
+            assert!((0..6).map(|steps| cube.rotate(steps)).all(balanced));
+            assert!([cube.reflect_q(), cube.reflect_r(), cube.reflect_s()].into_iter().all(balanced));
+            assert_eq!(cube.rotate(6), cube);
+        }
+    }
+}
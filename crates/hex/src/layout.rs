@@ -0,0 +1,98 @@
+use std::f32::consts::PI;
+
+use crate::fractional::FractionalHex;
+use crate::hexagon::{Axial, HexNum};
+
+const SQRT_3: f32 = 1.732_050_8;
+const HALF_SQRT_3: f32 = SQRT_3 / 2.0;
+
+/// The forward and inverse matrices used to convert between hex and world space for a hex orientation
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Orientation {
+    forward: [f32; 4],
+    inverse: [f32; 4],
+    start_angle: f32,
+}
+
+impl Orientation {
+    pub const POINTY: Orientation = Orientation {
+        forward: [SQRT_3, HALF_SQRT_3, 0.0, 1.5],
+        inverse: [SQRT_3 / 3.0, -1.0 / 3.0, 0.0, 2.0 / 3.0],
+        start_angle: 0.5,
+    };
+
+    pub const FLAT: Orientation = Orientation {
+        forward: [1.5, 0.0, HALF_SQRT_3, SQRT_3],
+        inverse: [2.0 / 3.0, 0.0, -1.0 / 3.0, SQRT_3 / 3.0],
+        start_angle: 0.0,
+    };
+}
+
+/// Describes how hexes are placed in world space on the XZ plane.
+/// `size` is the distance from a hex center to its corners along each axis, `origin` is the world position of hex (0, 0).
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Layout {
+    pub orientation: Orientation,
+    pub size: (f32, f32),
+    pub origin: (f32, f32),
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout::pointy(1.0)
+    }
+}
+
+impl Layout {
+    pub fn new(orientation: Orientation, size: (f32, f32), origin: (f32, f32)) -> Self {
+        Layout { orientation, size, origin }
+    }
+
+    /// A pointy-top layout with uniform size centered at the world origin
+    pub fn pointy(size: f32) -> Self {
+        Layout::new(Orientation::POINTY, (size, size), (0.0, 0.0))
+    }
+
+    /// A flat-top layout with uniform size centered at the world origin
+    pub fn flat(size: f32) -> Self {
+        Layout::new(Orientation::FLAT, (size, size), (0.0, 0.0))
+    }
+
+    pub fn hex_to_world<T: HexNum>(&self, hex: Axial<T>) -> (f32, f32) {
+        let m = &self.orientation.forward;
+        let q = hex.q().to_f32().unwrap_or_default();
+        let r = hex.r().to_f32().unwrap_or_default();
+
+        let x = (m[0] * q + m[1] * r) * self.size.0;
+        let z = (m[2] * q + m[3] * r) * self.size.1;
+
+        (x + self.origin.0, z + self.origin.1)
+    }
+
+    pub fn world_to_hex(&self, x: f32, z: f32) -> FractionalHex {
+        let m = &self.orientation.inverse;
+        let x = (x - self.origin.0) / self.size.0;
+        let z = (z - self.origin.1) / self.size.1;
+
+        let q = m[0] * x + m[1] * z;
+        let r = m[2] * x + m[3] * z;
+
+        FractionalHex::new(q, r, -q - r)
+    }
+
+    /// Offset from a hex center to one of its six corners, counted counter-clockwise from the start angle
+    pub fn corner_offset(&self, corner: usize) -> (f32, f32) {
+        let angle = 2.0 * PI * (self.orientation.start_angle + corner as f32) / 6.0;
+        (self.size.0 * angle.cos(), self.size.1 * angle.sin())
+    }
+
+    pub fn corners<T: HexNum>(&self, hex: Axial<T>) -> [(f32, f32); 6] {
+        let (x, z) = self.hex_to_world(hex);
+        std::array::from_fn(|i| {
+            let offset = self.corner_offset(i);
+            (x + offset.0, z + offset.1)
+        })
+    }
+}
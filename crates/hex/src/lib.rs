@@ -1,6 +1,15 @@
 // use std::ops;
 
 pub mod hexagon;
+pub mod binary;
+pub mod chunk;
+pub mod fractional;
+pub mod layout;
+pub mod line;
+pub mod map;
+pub mod offset;
+pub mod path;
+pub mod pattern;
 
 // const SQRT_3: f32 = 1.73205080757;
 // const HALF_SQRT_3: f32 = SQRT_3 / 2.0;
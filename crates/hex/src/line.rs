@@ -0,0 +1,127 @@
+use std::collections::HashSet;
+
+use crate::fractional::FractionalHex;
+use crate::hexagon::{self, Axial};
+
+// Nudges line end points off hex edges so that rounding is consistent along the line
+const EPSILON: (f32, f32, f32) = (1e-6, 1e-6, -2e-6);
+
+impl Axial<i32> {
+    /// Iterates every hex on the straight line from this hex to `other`, including both end points.
+    /// The line is traced relative to this hex, so translating both end points translates the line unchanged.
+    pub fn line_to(&self, other: &Axial<i32>) -> HexLine {
+        // Kept near the origin, where the nudge is not lost to rounding as it would be at large coordinates
+        let nudge = |hex: Axial<i32>| {
+            let f = FractionalHex::from(hex);
+            FractionalHex::new(f.q() + EPSILON.0, f.r() + EPSILON.1, f.s() + EPSILON.2)
+        };
+
+        HexLine {
+            origin: *self,
+            start: nudge(Axial::new(0, 0)),
+            end: nudge(*other - *self),
+            steps: self.distance(other),
+            index: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HexLine {
+    origin: Axial<i32>,
+    start: FractionalHex,
+    end: FractionalHex,
+    steps: i32,
+    index: i32,
+}
+
+impl Iterator for HexLine {
+    type Item = Axial<i32>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index > self.steps {
+            return None;
+        }
+
+        let t = if self.steps == 0 { 0.0 } else { self.index as f32 / self.steps as f32 };
+        self.index += 1;
+
+        Some(self.origin + self.start.lerp(&self.end, t).round())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.steps + 1 - self.index).max(0) as usize;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for HexLine {}
+
+/// Returns true if no hex strictly between `from` and `to` is blocking.
+/// The end points themselves never block, so a wall can be seen but not seen through.
+pub fn line_of_sight<F>(from: Axial<i32>, to: Axial<i32>, mut is_blocking: F) -> bool
+where
+    F: FnMut(Axial<i32>) -> bool,
+{
+    let steps = from.distance(&to) as usize;
+    from.line_to(&to)
+        .skip(1)
+        .take(steps.saturating_sub(1))
+        .all(|hex| !is_blocking(hex))
+}
+
+/// Collects every hex within `radius` of `origin` that has line of sight back to it
+pub fn field_of_view<F>(origin: Axial<i32>, radius: i32, mut is_blocking: F) -> HashSet<Axial<i32>>
+where
+    F: FnMut(Axial<i32>) -> bool,
+{
+    hexagon::range(origin, radius)
+        .filter(|hex| line_of_sight(origin, *hex, &mut is_blocking))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_include_both_end_points_and_step_to_neighbors() {
+        let from = Axial::new(-3, 5);
+        for to in hexagon::range(from, 6) {
+            let line = from.line_to(&to).collect::<Vec<_>>();
+            assert_eq!(line.len(), from.distance(&to) as usize + 1);
+            assert_eq!(line.first(), Some(&from));
+            assert_eq!(line.last(), Some(&to));
+            assert!(line.windows(2).all(|pair| pair[0].distance(&pair[1]) == 1), "{line:?}");
+        }
+    }
+
+    #[test]
+    fn lines_keep_their_shape_when_translated() {
+        let offsets = [Axial::new(1000, 1000), Axial::new(-40_000, 25_000), Axial::new(3_000_000, -7_000_000)];
+        for delta in hexagon::range(Axial::new(0, 0), 8) {
+            let line = Axial::new(0, 0).line_to(&delta).collect::<Vec<_>>();
+            for offset in offsets {
+                let moved = offset.line_to(&(offset + delta)).map(|hex| hex - offset).collect::<Vec<_>>();
+                assert_eq!(moved, line, "line to {delta:?} changed shape at {offset:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn field_of_view_does_not_depend_on_position() {
+        let walls = [Axial::new(1, 0), Axial::new(-1, 2), Axial::new(0, -2)];
+        let view = |origin: Axial<i32>| {
+            let mut seen = field_of_view(origin, 5, |hex| walls.contains(&(hex - origin)))
+                .into_iter()
+                .map(|hex| hex - origin)
+                .collect::<Vec<_>>();
+            seen.sort_by_key(|hex| (hex.q(), hex.r()));
+            seen
+        };
+
+        let near = view(Axial::new(0, 0));
+        assert!(!near.contains(&Axial::new(2, 0)));
+        assert_eq!(view(Axial::new(5000, -2000)), near);
+    }
+}
@@ -0,0 +1,371 @@
+use std::ops;
+
+use crate::hexagon::Axial;
+
+/// The overall shape of a `HexMap`, which decides which hexes it stores
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MapShape {
+    /// Every hex within `radius` steps of the origin
+    Hexagon { radius: i32 },
+    /// A rectangle of `width` by `height` hexes for a pointy-top layout, covering odd-r offset columns `0..width` and rows `0..height`
+    Rectangle { width: i32, height: i32 },
+    /// Every hex with `q` in `q_min..=q_max` and `r` in `r_min..=r_max`
+    Parallelogram { q_min: i32, q_max: i32, r_min: i32, r_max: i32 },
+    /// The triangle of hexes with `q >= 0`, `r >= 0` and `q + r <= size`
+    Triangle { size: i32 },
+}
+
+impl MapShape {
+    /// Empty for invalid shapes, whose sizes would otherwise overflow here
+    fn r_range(&self) -> ops::RangeInclusive<i32> {
+        if self.hex_count().is_none() {
+            return ops::RangeInclusive::new(1, 0);
+        }
+        match *self {
+            MapShape::Hexagon { radius } => -radius..=radius,
+            MapShape::Rectangle { height, .. } => 0..=height - 1,
+            MapShape::Parallelogram { r_min, r_max, .. } => r_min..=r_max,
+            MapShape::Triangle { size } => 0..=size,
+        }
+    }
+
+    fn q_range(&self, r: i32) -> ops::RangeInclusive<i32> {
+        match *self {
+            MapShape::Hexagon { radius } => (-radius).max(-r - radius)..=radius.min(-r + radius),
+            MapShape::Rectangle { width, .. } => {
+                let offset = r.div_euclid(2);
+                -offset..=width - 1 - offset
+            },
+            MapShape::Parallelogram { q_min, q_max, .. } => q_min..=q_max,
+            MapShape::Triangle { size } => 0..=size - r,
+        }
+    }
+
+    /// Number of hexes in the shape, computed without visiting them. `None` if a size is negative, a minimum is
+    /// above its maximum, a hexagon's radius exceeds `i32::MAX / 2` or the count does not fit in a `usize`.
+    pub fn hex_count(&self) -> Option<usize> {
+        let count = match *self {
+            MapShape::Hexagon { radius } if (0..=i32::MAX / 2).contains(&radius) => {
+                let radius = radius as i64;
+                3 * radius * (radius + 1) + 1
+            },
+            MapShape::Rectangle { width, height } if width >= 0 && height >= 0 => width as i64 * height as i64,
+            MapShape::Parallelogram { q_min, q_max, r_min, r_max } if q_min <= q_max && r_min <= r_max => {
+                (q_max as i64 - q_min as i64 + 1).checked_mul(r_max as i64 - r_min as i64 + 1)?
+            },
+            MapShape::Triangle { size } if size >= 0 => {
+                let size = size as i64;
+                (size + 1) * (size + 2) / 2
+            },
+            _ => return None,
+        };
+        usize::try_from(count).ok()
+    }
+
+    /// Whether `hex` is in the shape. Always false for an invalid shape.
+    pub fn contains(&self, hex: Axial<i32>) -> bool {
+        self.r_range().contains(&hex.r()) && self.q_range(hex.r()).contains(&hex.q())
+    }
+
+    /// Iterates every hex in the shape, ordered by `r` and then `q`. Empty for an invalid shape.
+    pub fn coords(&self) -> impl Iterator<Item = Axial<i32>> {
+        let shape = *self;
+        shape.r_range().flat_map(move |r| shape.q_range(r).map(move |q| Axial::new(q, r)))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Row {
+    q_min: i32,
+    len: usize,
+    start: usize,
+}
+
+/// Dense per-hex storage for a fixed `MapShape`.
+/// Every hex in the shape always has a value, and lookups are a row offset plus a column offset.
+#[derive(Debug, Clone)]
+pub struct HexMap<T> {
+    shape: MapShape,
+    r_min: i32,
+    rows: Vec<Row>,
+    data: Vec<T>,
+}
+
+impl<T> HexMap<T> {
+    /// Creates a map by calling `f` for every hex in coordinate order.
+    /// Panics if the shape is invalid, see `MapShape::hex_count`.
+    pub fn from_fn<F>(shape: MapShape, f: F) -> Self
+    where
+        F: FnMut(Axial<i32>) -> T,
+    {
+        if shape.hex_count().is_none() {
+            panic!("Invalid map shape {shape:?}, sizes must not be negative and minimums must not exceed maximums");
+        }
+        let data = shape.coords().map(f).collect::<Vec<_>>();
+        Self::from_values(shape, data).expect("Shape coordinates and values are the same length")
+    }
+
+    /// Creates a map from values listed in coordinate order, or `None` if the shape is invalid or the number of values does not match it
+    pub fn from_values(shape: MapShape, data: Vec<T>) -> Option<Self> {
+        // Checked before walking the rows so that huge or malformed shapes are rejected without iterating them
+        if shape.hex_count()? != data.len() {
+            return None;
+        }
+
+        let r_range = shape.r_range();
+        let r_min = *r_range.start();
+
+        let mut rows = Vec::new();
+        let mut start = 0;
+        for r in r_range {
+            let q_range = shape.q_range(r);
+            let len = q_range.clone().count();
+            rows.push(Row { q_min: *q_range.start(), len, start });
+            start += len;
+        }
+
+        debug_assert_eq!(start, data.len());
+        Some(HexMap { shape, r_min, rows, data })
+    }
+
+    /// Creates a map holding `value` at every hex. Panics if the shape is invalid, see `MapShape::hex_count`.
+    pub fn filled(shape: MapShape, value: T) -> Self
+    where
+        T: Clone,
+    {
+        Self::from_fn(shape, |_| value.clone())
+    }
+
+    #[inline]
+    pub fn shape(&self) -> MapShape {
+        self.shape
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn index_of(&self, hex: Axial<i32>) -> Option<usize> {
+        let row = self.rows.get(usize::try_from(hex.r().checked_sub(self.r_min)?).ok()?)?;
+        let column = usize::try_from(hex.q().checked_sub(row.q_min)?).ok()?;
+        (column < row.len).then_some(row.start + column)
+    }
+
+    #[inline]
+    pub fn contains(&self, hex: Axial<i32>) -> bool {
+        self.index_of(hex).is_some()
+    }
+
+    pub fn get(&self, hex: Axial<i32>) -> Option<&T> {
+        self.index_of(hex).map(|i| &self.data[i])
+    }
+
+    pub fn get_mut(&mut self, hex: Axial<i32>) -> Option<&mut T> {
+        self.index_of(hex).map(|i| &mut self.data[i])
+    }
+
+    /// Replaces the value at `hex`, returning the previous value, or `None` if the hex is outside the map
+    pub fn set(&mut self, hex: Axial<i32>, value: T) -> Option<T> {
+        self.get_mut(hex).map(|v| std::mem::replace(v, value))
+    }
+
+    /// Iterates every hex in the map, ordered by `r` and then `q`
+    pub fn coords(&self) -> impl Iterator<Item = Axial<i32>> {
+        self.shape.coords()
+    }
+
+    pub fn values(&self) -> std::slice::Iter<'_, T> {
+        self.data.iter()
+    }
+
+    pub fn values_mut(&mut self) -> std::slice::IterMut<'_, T> {
+        self.data.iter_mut()
+    }
+
+    /// Consumes the map, returning its values in coordinate order
+    pub fn into_values(self) -> Vec<T> {
+        self.data
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Axial<i32>, &T)> {
+        self.shape.coords().zip(self.data.iter())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Axial<i32>, &mut T)> {
+        self.shape.coords().zip(self.data.iter_mut())
+    }
+
+    /// Creates a map of the same shape by transforming every value
+    pub fn map_values<U, F>(&self, mut f: F) -> HexMap<U>
+    where
+        F: FnMut(Axial<i32>, &T) -> U,
+    {
+        HexMap {
+            shape: self.shape,
+            r_min: self.r_min,
+            rows: self.rows.clone(),
+            data: self.iter().map(|(hex, value)| f(hex, value)).collect(),
+        }
+    }
+}
+
+impl<T> ops::Index<Axial<i32>> for HexMap<T> {
+    type Output = T;
+
+    fn index(&self, hex: Axial<i32>) -> &Self::Output {
+        self.get(hex).expect("Hex is outside of the map")
+    }
+}
+
+impl<T> ops::IndexMut<Axial<i32>> for HexMap<T> {
+    fn index_mut(&mut self, hex: Axial<i32>) -> &mut Self::Output {
+        self.get_mut(hex).expect("Hex is outside of the map")
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for HexMap<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("HexMap", 2)?;
+        state.serialize_field("shape", &self.shape)?;
+        state.serialize_field("values", &self.data)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for HexMap<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        #[serde(rename = "HexMap")]
+        struct Raw<T> {
+            shape: MapShape,
+            values: Vec<T>,
+        }
+
+        let raw = Raw::<T>::deserialize(deserializer)?;
+        let expected = raw.shape.hex_count()
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid HexMap shape {:?}", raw.shape)))?;
+        let found = raw.values.len();
+        HexMap::from_values(raw.shape, raw.values)
+            .ok_or_else(|| serde::de::Error::custom(format!("HexMap shape holds {expected} values but {found} were provided")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHAPES: [MapShape; 4] = [
+        MapShape::Hexagon { radius: 3 },
+        MapShape::Rectangle { width: 4, height: 5 },
+        MapShape::Parallelogram { q_min: -2, q_max: 3, r_min: -1, r_max: 2 },
+        MapShape::Triangle { size: 4 },
+    ];
+
+    #[test]
+    fn every_coordinate_is_indexed_once() {
+        for shape in SHAPES {
+            let map = HexMap::from_fn(shape, |hex| hex);
+            assert_eq!(map.len(), shape.coords().count());
+            assert_eq!(shape.hex_count(), Some(map.len()));
+            for (hex, value) in map.iter() {
+                assert!(shape.contains(hex));
+                assert_eq!(map.get(hex), Some(value));
+                assert_eq!(*value, hex);
+            }
+        }
+    }
+
+    #[test]
+    fn hex_count_matches_coords() {
+        for n in 0..12 {
+            let shapes = [
+                MapShape::Hexagon { radius: n },
+                MapShape::Rectangle { width: n, height: 7 - n / 2 },
+                MapShape::Parallelogram { q_min: -n, q_max: 2, r_min: n - 3, r_max: n },
+                MapShape::Triangle { size: n },
+            ];
+            for shape in shapes {
+                assert_eq!(shape.hex_count(), Some(shape.coords().count()), "{shape:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_shapes_have_no_count() {
+        let shapes = [
+            MapShape::Hexagon { radius: -1 },
+            MapShape::Hexagon { radius: i32::MAX },
+            MapShape::Rectangle { width: 3, height: i32::MIN },
+            MapShape::Rectangle { width: -1, height: 3 },
+            MapShape::Parallelogram { q_min: 1, q_max: 0, r_min: 0, r_max: 0 },
+            MapShape::Triangle { size: -4 },
+        ];
+        for shape in shapes {
+            assert_eq!(shape.hex_count(), None, "{shape:?}");
+            assert!(HexMap::<u8>::from_values(shape, Vec::new()).is_none());
+        }
+    }
+
+    #[test]
+    fn extreme_shape_parameters_contain_nothing() {
+        let shapes = [
+            MapShape::Hexagon { radius: i32::MIN },
+            MapShape::Hexagon { radius: i32::MAX },
+            MapShape::Rectangle { width: i32::MIN, height: i32::MIN },
+            MapShape::Rectangle { width: i32::MAX, height: i32::MIN },
+            MapShape::Parallelogram { q_min: i32::MAX, q_max: i32::MIN, r_min: i32::MAX, r_max: i32::MIN },
+            MapShape::Triangle { size: i32::MIN },
+        ];
+        for shape in shapes {
+            assert_eq!(shape.coords().next(), None, "{shape:?}");
+            for hex in [Axial::new(0, 0), Axial::new(i32::MIN, i32::MIN), Axial::new(i32::MAX, i32::MIN), Axial::new(-1, 1)] {
+                assert!(!shape.contains(hex), "{hex:?} in {shape:?}");
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid map shape Hexagon { radius: -1 }")]
+    fn filling_an_invalid_shape_names_it() {
+        HexMap::filled(MapShape::Hexagon { radius: -1 }, 0u8);
+    }
+
+    #[test]
+    fn far_coordinates_are_outside() {
+        let extremes = [i32::MIN, i32::MIN + 1, -1_000_000, 1_000_000, i32::MAX - 1, i32::MAX];
+        for shape in SHAPES {
+            let mut map = HexMap::filled(shape, 0u8);
+            for q in extremes {
+                for r in extremes.into_iter().chain([0]) {
+                    let hex = Axial::new(q, r);
+                    assert!(!map.contains(hex), "{hex:?} in {shape:?}");
+                    assert_eq!(map.get(hex), None);
+                    assert_eq!(map.set(hex, 1), None);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn neighbors_of_the_edge_are_outside() {
+        for shape in SHAPES {
+            let map = HexMap::filled(shape, ());
+            for hex in map.coords() {
+                for neighbor in hex.neighbors() {
+                    assert_eq!(map.contains(neighbor), shape.contains(neighbor));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,239 @@
+use crate::hexagon::{Axial, Cube};
+
+/// Which rows or columns are shoved by half a hex in an offset coordinate system.
+/// The `R` variants shift rows and suit pointy-top layouts, the `Q` variants shift columns and suit flat-top layouts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OffsetKind {
+    OddR,
+    EvenR,
+    OddQ,
+    EvenQ,
+}
+
+/// A row and column position, as used by rectangular maps stored in 2D arrays
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OffsetCoord {
+    pub col: i32,
+    pub row: i32,
+    pub kind: OffsetKind,
+}
+
+impl OffsetCoord {
+    pub fn new(col: i32, row: i32, kind: OffsetKind) -> Self {
+        OffsetCoord { col, row, kind }
+    }
+
+    pub fn from_axial(hex: Axial<i32>, kind: OffsetKind) -> Self {
+        let (q, r) = (hex.q(), hex.r());
+        let (col, row) = match kind {
+            OffsetKind::OddR => (q + (r - (r & 1)) / 2, r),
+            OffsetKind::EvenR => (q + (r + (r & 1)) / 2, r),
+            OffsetKind::OddQ => (q, r + (q - (q & 1)) / 2),
+            OffsetKind::EvenQ => (q, r + (q + (q & 1)) / 2),
+        };
+
+        OffsetCoord { col, row, kind }
+    }
+
+    pub fn to_axial(&self) -> Axial<i32> {
+        let (col, row) = (self.col, self.row);
+        match self.kind {
+            OffsetKind::OddR => Axial::new(col - (row - (row & 1)) / 2, row),
+            OffsetKind::EvenR => Axial::new(col - (row + (row & 1)) / 2, row),
+            OffsetKind::OddQ => Axial::new(col, row - (col - (col & 1)) / 2),
+            OffsetKind::EvenQ => Axial::new(col, row - (col + (col & 1)) / 2),
+        }
+    }
+
+    pub fn from_cube(hex: Cube<i32>, kind: OffsetKind) -> Self {
+        Self::from_axial(hex.to_axial(), kind)
+    }
+
+    pub fn to_cube(&self) -> Cube<i32> {
+        self.to_axial().to_cube()
+    }
+}
+
+impl From<OffsetCoord> for Axial<i32> {
+    fn from(value: OffsetCoord) -> Self {
+        value.to_axial()
+    }
+}
+
+impl From<OffsetCoord> for Cube<i32> {
+    fn from(value: OffsetCoord) -> Self {
+        value.to_cube()
+    }
+}
+
+/// Which axis steps by two in a doubled coordinate system.
+/// `Width` doubles columns and suits pointy-top layouts, `Height` doubles rows and suits flat-top layouts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DoubledKind {
+    Width,
+    Height,
+}
+
+/// A row and column position where neighboring columns (or rows) step by two, so `col + row` is always even
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DoubledCoord {
+    pub col: i32,
+    pub row: i32,
+    pub kind: DoubledKind,
+}
+
+impl DoubledCoord {
+    pub fn new(col: i32, row: i32, kind: DoubledKind) -> Self {
+        DoubledCoord { col, row, kind }
+    }
+
+    /// Doubled coordinates only name a hex when `col + row` is even
+    pub fn is_valid(&self) -> bool {
+        (self.col + self.row) % 2 == 0
+    }
+
+    pub fn from_axial(hex: Axial<i32>, kind: DoubledKind) -> Self {
+        let (q, r) = (hex.q(), hex.r());
+        let (col, row) = match kind {
+            DoubledKind::Width => (2 * q + r, r),
+            DoubledKind::Height => (q, 2 * r + q),
+        };
+
+        DoubledCoord { col, row, kind }
+    }
+
+    pub fn to_axial(&self) -> Axial<i32> {
+        debug_assert!(self.is_valid(), "Doubled coordinates must satisfy (col + row) % 2 == 0");
+        match self.kind {
+            DoubledKind::Width => Axial::new((self.col - self.row) / 2, self.row),
+            DoubledKind::Height => Axial::new(self.col, (self.row - self.col) / 2),
+        }
+    }
+
+    pub fn from_cube(hex: Cube<i32>, kind: DoubledKind) -> Self {
+        Self::from_axial(hex.to_axial(), kind)
+    }
+
+    pub fn to_cube(&self) -> Cube<i32> {
+        self.to_axial().to_cube()
+    }
+}
+
+impl From<DoubledCoord> for Axial<i32> {
+    fn from(value: DoubledCoord) -> Self {
+        value.to_axial()
+    }
+}
+
+impl From<DoubledCoord> for Cube<i32> {
+    fn from(value: DoubledCoord) -> Self {
+        value.to_cube()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hexagon::spiral;
+    use crate::map::MapShape;
+
+    const OFFSET_KINDS: [OffsetKind; 4] = [OffsetKind::OddR, OffsetKind::EvenR, OffsetKind::OddQ, OffsetKind::EvenQ];
+
+    fn sorted(mut offsets: Vec<(i32, i32)>) -> Vec<(i32, i32)> {
+        offsets.sort();
+        offsets
+    }
+
+    #[test]
+    fn offset_round_trips() {
+        for kind in OFFSET_KINDS {
+            for hex in spiral(Axial::new(-3, 2), 6) {
+                let offset = OffsetCoord::from_axial(hex, kind);
+                assert_eq!(offset.to_axial(), hex, "{kind:?}");
+                assert_eq!(Axial::from(offset), hex);
+                assert_eq!(OffsetCoord::from_cube(hex.to_cube(), kind), offset);
+                assert_eq!(Cube::from(offset), hex.to_cube());
+            }
+        }
+    }
+
+    #[test]
+    fn doubled_round_trips() {
+        for kind in [DoubledKind::Width, DoubledKind::Height] {
+            for hex in spiral(Axial::new(4, -1), 6) {
+                let doubled = DoubledCoord::from_axial(hex, kind);
+                assert!(doubled.is_valid(), "{doubled:?}");
+                assert_eq!(doubled.to_axial(), hex);
+                assert_eq!(Axial::from(doubled), hex);
+                assert_eq!(DoubledCoord::from_cube(hex.to_cube(), kind), doubled);
+                assert_eq!(Cube::from(doubled), hex.to_cube());
+            }
+        }
+        assert!(!DoubledCoord::new(1, 0, DoubledKind::Width).is_valid());
+    }
+
+    #[test]
+    fn offset_neighbors_depend_on_row_and_column_parity() {
+        // Column and row steps to the six neighbors, for an even then an odd row (or column for the `Q` kinds)
+        let shoved_right = vec![(1, 0), (1, -1), (0, -1), (-1, 0), (0, 1), (1, 1)];
+        let shoved_left = vec![(1, 0), (0, -1), (-1, -1), (-1, 0), (-1, 1), (0, 1)];
+        let shoved_down = vec![(1, 0), (1, 1), (0, -1), (-1, 0), (-1, 1), (0, 1)];
+        let shoved_up = vec![(1, 0), (1, -1), (0, -1), (-1, -1), (-1, 0), (0, 1)];
+        let expected = [
+            (OffsetKind::OddR, &shoved_left, &shoved_right),
+            (OffsetKind::EvenR, &shoved_right, &shoved_left),
+            (OffsetKind::OddQ, &shoved_up, &shoved_down),
+            (OffsetKind::EvenQ, &shoved_down, &shoved_up),
+        ];
+
+        for (kind, even, odd) in expected {
+            for (col, row) in [(2, 4), (2, 3), (-3, -2), (-3, -5), (0, 0), (1, 1)] {
+                let offset = OffsetCoord::new(col, row, kind);
+                let steps = offset.to_axial().neighbors().iter()
+                    .map(|&n| OffsetCoord::from_axial(n, kind))
+                    .map(|n| (n.col - col, n.row - row))
+                    .collect();
+                let parity = match kind {
+                    OffsetKind::OddR | OffsetKind::EvenR => row,
+                    OffsetKind::OddQ | OffsetKind::EvenQ => col,
+                };
+                let expected = if parity.rem_euclid(2) == 0 { even } else { odd };
+                assert_eq!(sorted(steps), sorted(expected.clone()), "{offset:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn doubled_neighbors_are_fixed_steps() {
+        let expected = [
+            (DoubledKind::Width, vec![(2, 0), (1, -1), (-1, -1), (-2, 0), (-1, 1), (1, 1)]),
+            (DoubledKind::Height, vec![(1, 1), (1, -1), (0, -2), (-1, -1), (-1, 1), (0, 2)]),
+        ];
+        for (kind, expected) in expected {
+            for hex in spiral(Axial::new(0, 0), 2) {
+                let doubled = DoubledCoord::from_axial(hex, kind);
+                let steps = hex.neighbors().iter()
+                    .map(|&n| DoubledCoord::from_axial(n, kind))
+                    .map(|n| (n.col - doubled.col, n.row - doubled.row))
+                    .collect();
+                assert_eq!(sorted(steps), sorted(expected.clone()), "{doubled:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn rectangle_maps_cover_odd_r_offsets() {
+        let shape = MapShape::Rectangle { width: 5, height: 4 };
+        let mut offsets = shape.coords()
+            .map(|hex| OffsetCoord::from_axial(hex, OffsetKind::OddR))
+            .map(|offset| (offset.col, offset.row))
+            .collect::<Vec<_>>();
+        offsets.sort();
+        let expected = (0..5).flat_map(|col| (0..4).map(move |row| (col, row))).collect::<Vec<_>>();
+        assert_eq!(offsets, expected);
+    }
+}
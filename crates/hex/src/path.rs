@@ -0,0 +1,333 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::hexagon::Axial;
+use crate::map::HexMap;
+
+// Costs returned by the cost callbacks are the price of stepping from the first hex onto the second.
+// `None` marks the step as impassable. A* assumes every passable step costs at least 1.
+// The plane is unbounded, so the callback (or `max_cost`) is what keeps a search from running forever.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Path {
+    pub hexes: Vec<Axial<i32>>,
+    pub cost: u32,
+}
+
+/// Extra movement cost for changing elevation between neighboring tiles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClimbCost {
+    /// Cost per unit of elevation gained
+    pub up: u32,
+    /// Cost per unit of elevation lost
+    pub down: u32,
+    /// Steps with a larger elevation difference than this are impassable
+    pub max_step: Option<u32>,
+}
+
+impl Default for ClimbCost {
+    fn default() -> Self {
+        ClimbCost { up: 1, down: 0, max_step: None }
+    }
+}
+
+impl ClimbCost {
+    pub fn cost(&self, from: i32, to: i32) -> Option<u32> {
+        let difference = to.abs_diff(from);
+        if self.max_step.is_some_and(|max| difference > max) {
+            return None;
+        }
+
+        Some(if to > from { difference * self.up } else { difference * self.down })
+    }
+}
+
+/// Builds a cost callback from a `HexMap`. `tile_cost` is the cost of entering a tile, or `None` if it is impassable,
+/// and `elevation` is combined with `climb` to charge for moving up and down hill. Hexes outside the map are impassable.
+pub fn terrain_cost<'a, T, C, E>(map: &'a HexMap<T>, tile_cost: C, elevation: E, climb: ClimbCost) -> impl Fn(Axial<i32>, Axial<i32>) -> Option<u32> + 'a
+where
+    C: Fn(&T) -> Option<u32> + 'a,
+    E: Fn(&T) -> i32 + 'a,
+{
+    move |from, to| {
+        let from = map.get(from)?;
+        let to = map.get(to)?;
+        let climb_cost = climb.cost(elevation(from), elevation(to))?;
+        Some(tile_cost(to)? + climb_cost)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Frontier {
+    priority: u32,
+    hex: Axial<i32>,
+}
+
+impl Ord for Frontier {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed so the binary heap pops the lowest priority first, ties are broken by coordinate to keep searches deterministic
+        other.priority.cmp(&self.priority)
+            .then_with(|| (other.hex.q(), other.hex.r()).cmp(&(self.hex.q(), self.hex.r())))
+    }
+}
+
+impl PartialOrd for Frontier {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Finds the cheapest path from `start` to `goal` using hex distance as the heuristic
+pub fn astar<C>(start: Axial<i32>, goal: Axial<i32>, mut cost: C) -> Option<Path>
+where
+    C: FnMut(Axial<i32>, Axial<i32>) -> Option<u32>,
+{
+    let mut frontier = BinaryHeap::new();
+    let mut came_from = HashMap::new();
+    let mut cost_so_far = HashMap::new();
+
+    frontier.push(Frontier { priority: 0, hex: start });
+    came_from.insert(start, None);
+    cost_so_far.insert(start, 0);
+
+    while let Some(Frontier { hex: current, .. }) = frontier.pop() {
+        if current == goal {
+            return Some(Path { hexes: reconstruct(&came_from, goal), cost: cost_so_far[&goal] });
+        }
+
+        let current_cost = cost_so_far[&current];
+        for next in current.neighbors() {
+            let Some(step) = cost(current, next) else { continue };
+            let new_cost = current_cost + step;
+
+            if cost_so_far.get(&next).is_none_or(|&c| new_cost < c) {
+                cost_so_far.insert(next, new_cost);
+                came_from.insert(next, Some(current));
+                let priority = new_cost + next.distance(&goal) as u32;
+                frontier.push(Frontier { priority, hex: next });
+            }
+        }
+    }
+
+    None
+}
+
+fn reconstruct(came_from: &HashMap<Axial<i32>, Option<Axial<i32>>>, goal: Axial<i32>) -> Vec<Axial<i32>> {
+    let mut hexes = vec![goal];
+    let mut current = goal;
+    while let Some(&Some(previous)) = came_from.get(&current) {
+        hexes.push(previous);
+        current = previous;
+    }
+    hexes.reverse();
+    hexes
+}
+
+/// The result of a Dijkstra search, holding the cheapest cost and the next hex back towards the sources for every visited hex
+#[derive(Debug, Clone, Default)]
+pub struct FlowField {
+    costs: HashMap<Axial<i32>, u32>,
+    next: HashMap<Axial<i32>, Axial<i32>>,
+}
+
+impl FlowField {
+    pub fn cost(&self, hex: Axial<i32>) -> Option<u32> {
+        self.costs.get(&hex).copied()
+    }
+
+    /// The neighboring hex to move to from `hex` to follow the cheapest route, or `None` at a source or unvisited hex
+    pub fn next(&self, hex: Axial<i32>) -> Option<Axial<i32>> {
+        self.next.get(&hex).copied()
+    }
+
+    /// Follows the field from `hex` until it reaches a source
+    pub fn path(&self, hex: Axial<i32>) -> Option<Path> {
+        let cost = self.cost(hex)?;
+        let mut hexes = vec![hex];
+        let mut current = hex;
+        while let Some(next) = self.next(current) {
+            hexes.push(next);
+            current = next;
+        }
+        Some(Path { hexes, cost })
+    }
+
+    pub fn costs(&self) -> &HashMap<Axial<i32>, u32> {
+        &self.costs
+    }
+
+    pub fn into_costs(self) -> HashMap<Axial<i32>, u32> {
+        self.costs
+    }
+}
+
+/// Expands outwards from `sources` visiting every hex reachable for at most `max_cost`.
+/// Paths in the returned field lead back to the nearest source, and each step is charged as `cost(source side, far side)`.
+pub fn dijkstra<C>(sources: &[Axial<i32>], max_cost: Option<u32>, cost: C) -> FlowField
+where
+    C: FnMut(Axial<i32>, Axial<i32>) -> Option<u32>,
+{
+    search(sources, max_cost, cost)
+}
+
+/// Builds a flow field towards `goals`. Unlike `dijkstra`, steps are charged in the direction of travel towards the goal,
+/// which matters when costs are asymmetric, such as climbing.
+pub fn flow_field<C>(goals: &[Axial<i32>], max_cost: Option<u32>, mut cost: C) -> FlowField
+where
+    C: FnMut(Axial<i32>, Axial<i32>) -> Option<u32>,
+{
+    search(goals, max_cost, move |from, to| cost(to, from))
+}
+
+/// Every hex a unit at `start` can reach with `movement_points`, along with the cheapest cost to get there
+pub fn reachable<C>(start: Axial<i32>, movement_points: u32, cost: C) -> HashMap<Axial<i32>, u32>
+where
+    C: FnMut(Axial<i32>, Axial<i32>) -> Option<u32>,
+{
+    dijkstra(&[start], Some(movement_points), cost).into_costs()
+}
+
+fn search<C>(sources: &[Axial<i32>], max_cost: Option<u32>, mut cost: C) -> FlowField
+where
+    C: FnMut(Axial<i32>, Axial<i32>) -> Option<u32>,
+{
+    let mut frontier = BinaryHeap::new();
+    let mut field = FlowField::default();
+
+    for &source in sources {
+        field.costs.insert(source, 0);
+        frontier.push(Frontier { priority: 0, hex: source });
+    }
+
+    while let Some(Frontier { priority, hex: current }) = frontier.pop() {
+        if priority > field.costs[&current] {
+            continue;
+        }
+
+        for next in current.neighbors() {
+            let Some(step) = cost(current, next) else { continue };
+            let new_cost = priority + step;
+
+            if max_cost.is_some_and(|max| new_cost > max) {
+                continue;
+            }
+
+            if field.costs.get(&next).is_none_or(|&c| new_cost < c) {
+                field.costs.insert(next, new_cost);
+                field.next.insert(next, current);
+                frontier.push(Frontier { priority: new_cost, hex: next });
+            }
+        }
+    }
+
+    field
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hexagon;
+    use crate::map::MapShape;
+
+    /// Tiles hold their entry cost, `None` for walls, and their elevation
+    type Tile = (Option<u32>, i32);
+
+    fn map_with(walls: &[Axial<i32>], elevation: impl Fn(Axial<i32>) -> i32) -> HexMap<Tile> {
+        HexMap::from_fn(MapShape::Hexagon { radius: 3 }, |hex| (if walls.contains(&hex) { None } else { Some(1) }, elevation(hex)))
+    }
+
+    fn cost(map: &HexMap<Tile>, climb: ClimbCost) -> impl Fn(Axial<i32>, Axial<i32>) -> Option<u32> + '_ {
+        terrain_cost(map, |tile: &Tile| tile.0, |tile: &Tile| tile.1, climb)
+    }
+
+    fn assert_walkable(path: &Path, map: &HexMap<Tile>) {
+        assert!(path.hexes.windows(2).all(|pair| pair[0].distance(&pair[1]) == 1), "{path:?}");
+        assert!(path.hexes.iter().all(|hex| map[*hex].0.is_some()), "{path:?}");
+    }
+
+    #[test]
+    fn walls_force_a_detour() {
+        let (start, goal) = (Axial::new(-2, 0), Axial::new(2, 0));
+        let open = map_with(&[], |_| 0);
+        assert_eq!(astar(start, goal, cost(&open, ClimbCost::default())).unwrap().cost, 4);
+
+        let walled = map_with(&[Axial::new(0, 0)], |_| 0);
+        let path = astar(start, goal, cost(&walled, ClimbCost::default())).unwrap();
+        assert_walkable(&path, &walled);
+        assert_eq!((path.hexes[0], *path.hexes.last().unwrap()), (start, goal));
+        assert_eq!(path.cost, 5);
+        assert_eq!(path.hexes.len(), 6);
+    }
+
+    #[test]
+    fn walled_off_goal_has_no_path() {
+        let goal = Axial::new(1, 1);
+        let map = map_with(&goal.neighbors(), |_| 0);
+        assert_eq!(astar(Axial::new(-2, -1), goal, cost(&map, ClimbCost::default())), None);
+        assert_eq!(dijkstra(&[Axial::new(-2, -1)], None, cost(&map, ClimbCost::default())).cost(goal), None);
+        assert_eq!(astar(goal, Axial::new(4, 0), cost(&map, ClimbCost::default())), None);
+    }
+
+    #[test]
+    fn climbing_costs_depend_on_direction() {
+        let climb = ClimbCost { up: 3, down: 0, max_step: None };
+        assert_eq!(climb.cost(0, 2), Some(6));
+        assert_eq!(climb.cost(2, 0), Some(0));
+        assert_eq!(ClimbCost { max_step: Some(1), ..climb }.cost(0, 2), None);
+
+        let hill = Axial::new(0, 0);
+        let below = Axial::new(1, 0);
+        let map = map_with(&[], |hex| if hex == hill { 2 } else { 0 });
+
+        // Every route onto the hill ends with the climb, so walking up costs the step plus two levels
+        let uphill = flow_field(&[hill], None, cost(&map, climb));
+        assert_eq!(uphill.cost(below), Some(7));
+        assert_eq!(uphill.next(below), Some(hill));
+        assert_eq!(uphill.path(below).unwrap().hexes, [below, hill]);
+
+        // Charged in the direction the search expands, which leaves the hill downhill
+        let downhill = dijkstra(&[hill], None, cost(&map, climb));
+        assert_eq!(downhill.cost(below), Some(1));
+
+        let steep = ClimbCost { max_step: Some(1), ..climb };
+        assert!(flow_field(&[hill], None, cost(&map, steep)).costs().keys().all(|hex| *hex == hill));
+    }
+
+    #[test]
+    fn reachable_stops_at_the_movement_points() {
+        let origin = Axial::new(5, -7);
+        let in_two = reachable(origin, 2, |_, _| Some(1));
+        let mut expected = hexagon::range(origin, 2).collect::<Vec<_>>();
+        let mut found = in_two.keys().copied().collect::<Vec<_>>();
+        expected.sort_by_key(|hex| (hex.q(), hex.r()));
+        found.sort_by_key(|hex| (hex.q(), hex.r()));
+        assert_eq!(found, expected);
+        assert!(in_two.iter().all(|(hex, &cost)| cost == origin.distance(hex) as u32));
+
+        let expensive = reachable(origin, 3, |_, _| Some(2));
+        assert_eq!(expensive.len(), 7);
+        assert_eq!(reachable(origin, 0, |_, _| Some(1)).len(), 1);
+    }
+
+    #[test]
+    fn astar_matches_dijkstra() {
+        let walls = [Axial::new(0, 0), Axial::new(1, -1), Axial::new(-1, 2), Axial::new(2, 1)];
+        let map = HexMap::from_fn(MapShape::Hexagon { radius: 4 }, |hex| {
+            let cost = 1 + (hex.q() * 7 + hex.r() * 13).rem_euclid(5) as u32;
+            (if walls.contains(&hex) { None } else { Some(cost) }, (hex.q() - hex.r()).rem_euclid(3))
+        });
+        let climb = ClimbCost { up: 2, down: 1, max_step: None };
+        let start = Axial::new(-3, 1);
+
+        let field = dijkstra(&[start], None, cost(&map, climb));
+        for goal in map.coords() {
+            let path = astar(start, goal, cost(&map, climb));
+            assert_eq!(path.as_ref().map(|p| p.cost), field.cost(goal), "{goal:?}");
+            if let Some(path) = path {
+                assert_walkable(&path, &map);
+                let steps = path.hexes.windows(2).map(|pair| cost(&map, climb)(pair[0], pair[1]).unwrap()).sum::<u32>();
+                assert_eq!(steps, path.cost);
+            }
+        }
+    }
+}
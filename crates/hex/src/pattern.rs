@@ -0,0 +1,100 @@
+use crate::hexagon::Axial;
+use crate::map::HexMap;
+
+/// A set of hexes relative to an anchor at the origin, each carrying a payload.
+/// Patterns can be rotated and mirrored around their anchor and then stamped into a `HexMap` at any position.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HexPattern<T> {
+    cells: Vec<(Axial<i32>, T)>,
+}
+
+impl<T> HexPattern<T> {
+    pub fn new() -> Self {
+        HexPattern { cells: Vec::new() }
+    }
+
+    /// Adds a payload at `offset` from the anchor, replacing any existing payload there
+    pub fn insert(&mut self, offset: Axial<i32>, value: T) {
+        match self.cells.iter_mut().find(|(o, _)| *o == offset) {
+            Some(cell) => cell.1 = value,
+            None => self.cells.push((offset, value)),
+        }
+    }
+
+    pub fn get(&self, offset: Axial<i32>) -> Option<&T> {
+        self.cells.iter().find(|(o, _)| *o == offset).map(|(_, v)| v)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Axial<i32>, &T)> {
+        self.cells.iter().map(|(o, v)| (*o, v))
+    }
+
+    fn transform<F: Fn(Axial<i32>) -> Axial<i32>>(mut self, f: F) -> Self {
+        for (offset, _) in self.cells.iter_mut() {
+            *offset = f(*offset);
+        }
+        self
+    }
+
+    /// Rotates around the anchor by `steps` multiples of 60 degrees, positive steps turning counter-clockwise
+    pub fn rotated(self, steps: i32) -> Self {
+        self.transform(|o| o.rotate(steps))
+    }
+
+    pub fn reflected_q(self) -> Self {
+        self.transform(|o| o.reflect_q())
+    }
+
+    pub fn reflected_r(self) -> Self {
+        self.transform(|o| o.reflect_r())
+    }
+
+    pub fn reflected_s(self) -> Self {
+        self.transform(|o| o.reflect_s())
+    }
+
+    /// Writes every payload into `map` with the anchor placed at `origin`, combining it with the existing tile through `apply`.
+    /// Cells that fall outside the map are skipped. Returns the number of tiles written.
+    pub fn stamp_with<U, F>(&self, map: &mut HexMap<U>, origin: Axial<i32>, mut apply: F) -> usize
+    where
+        F: FnMut(&mut U, &T),
+    {
+        let mut count = 0;
+        for (offset, value) in self.iter() {
+            if let Some(tile) = map.get_mut(origin + offset) {
+                apply(tile, value);
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Overwrites tiles in `map` with copies of the payloads, with the anchor placed at `origin`
+    pub fn stamp(&self, map: &mut HexMap<T>, origin: Axial<i32>) -> usize
+    where
+        T: Clone,
+    {
+        self.stamp_with(map, origin, |tile, value| *tile = value.clone())
+    }
+}
+
+impl<T> FromIterator<(Axial<i32>, T)> for HexPattern<T> {
+    fn from_iter<I: IntoIterator<Item = (Axial<i32>, T)>>(iter: I) -> Self {
+        let mut pattern = HexPattern::new();
+        for (offset, value) in iter {
+            pattern.insert(offset, value);
+        }
+        pattern
+    }
+}